@@ -2,6 +2,20 @@ pub use sea_orm_migration::prelude::*;
 
 mod m20230126_000001_create_board_table;
 mod m20230126_000002_create_paint_table;
+mod m20230210_000003_pack_color;
+mod m20230211_000004_create_paint_indexes;
+mod m20230215_000005_create_canvas_table;
+mod m20230217_000006_create_event_table;
+mod m20230218_000007_add_canvas_background;
+mod m20230219_000008_create_snapshot_table;
+mod m20230220_000009_create_audit_log_table;
+mod m20230221_000010_create_region_table;
+mod m20230222_000011_create_ban_table;
+mod m20230223_000012_create_shadowban_table;
+mod m20230223_000013_create_shadow_paint_table;
+mod m20230224_000014_create_bot_flag_table;
+mod m20230225_000015_create_session_log_table;
+mod m20230226_000016_add_paint_kind;
 
 pub struct Migrator;
 
@@ -11,6 +25,20 @@ impl MigratorTrait for Migrator {
     vec![
       Box::new(m20230126_000001_create_board_table::Migration),
       Box::new(m20230126_000002_create_paint_table::Migration),
+      Box::new(m20230210_000003_pack_color::Migration),
+      Box::new(m20230211_000004_create_paint_indexes::Migration),
+      Box::new(m20230215_000005_create_canvas_table::Migration),
+      Box::new(m20230217_000006_create_event_table::Migration),
+      Box::new(m20230218_000007_add_canvas_background::Migration),
+      Box::new(m20230219_000008_create_snapshot_table::Migration),
+      Box::new(m20230220_000009_create_audit_log_table::Migration),
+      Box::new(m20230221_000010_create_region_table::Migration),
+      Box::new(m20230222_000011_create_ban_table::Migration),
+      Box::new(m20230223_000012_create_shadowban_table::Migration),
+      Box::new(m20230223_000013_create_shadow_paint_table::Migration),
+      Box::new(m20230224_000014_create_bot_flag_table::Migration),
+      Box::new(m20230225_000015_create_session_log_table::Migration),
+      Box::new(m20230226_000016_add_paint_kind::Migration),
     ]
   }
 }
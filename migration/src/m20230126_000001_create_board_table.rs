@@ -15,7 +15,11 @@ impl MigrationTrait for Migration {
           .col(ColumnDef::new(Board::Y).integer().not_null())
           .col(ColumnDef::new(Board::Color).string_len(7).not_null())
           .col(ColumnDef::new(Board::Uid).integer().not_null())
-          .col(ColumnDef::new(Board::Time).timestamp().not_null())
+          .col(
+            ColumnDef::new(Board::Time)
+              .timestamp_with_time_zone()
+              .not_null(),
+          )
           .primary_key(Index::create().col(Board::X).col(Board::Y))
           .to_owned(),
       )
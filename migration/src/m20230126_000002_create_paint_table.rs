@@ -22,7 +22,11 @@ impl MigrationTrait for Migration {
           .col(ColumnDef::new(Paint::Y).integer().not_null())
           .col(ColumnDef::new(Paint::Color).string_len(7).not_null())
           .col(ColumnDef::new(Paint::Uid).integer().not_null())
-          .col(ColumnDef::new(Paint::Time).timestamp().not_null())
+          .col(
+            ColumnDef::new(Paint::Time)
+              .timestamp_with_time_zone()
+              .not_null(),
+          )
           .to_owned(),
       )
       .await
@@ -0,0 +1,116 @@
+use sea_orm_migration::{
+  prelude::*,
+  sea_orm::{ConnectionTrait, DatabaseBackend, Statement},
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// `#RRGGBB` in `Color` to `0xRRGGBB`
+fn hex_to_int(backend: DatabaseBackend) -> String {
+  match backend {
+    DatabaseBackend::Sqlite => (0..6)
+      .map(|i| {
+        format!(
+          "((instr('0123456789ABCDEF', upper(substr(color, {}, 1))) - 1) << {})",
+          i + 2,
+          (5 - i) * 4
+        )
+      })
+      .collect::<Vec<_>>()
+      .join(" | "),
+    DatabaseBackend::Postgres => "('x' || lpad(substr(color, 2), 8, '0'))::bit(32)::int".to_owned(),
+    DatabaseBackend::MySql => "CONV(SUBSTR(color, 2), 16, 10)".to_owned(),
+  }
+}
+
+/// `0xRRGGBB` in `Color` to `#RRGGBB`
+fn int_to_hex(backend: DatabaseBackend) -> &'static str {
+  match backend {
+    DatabaseBackend::Sqlite => "printf('#%06X', color)",
+    DatabaseBackend::Postgres => "'#' || upper(lpad(to_hex(color), 6, '0'))",
+    DatabaseBackend::MySql => "CONCAT('#', LPAD(HEX(color), 6, '0'))",
+  }
+}
+
+/// Replace `Color` of `table` with a column of type `def`, filled by `expr`.
+async fn convert(
+  manager: &SchemaManager<'_>,
+  table: Tables,
+  def: &mut ColumnDef,
+  expr: &str,
+) -> Result<(), DbErr> {
+  manager
+    .alter_table(
+      Table::alter()
+        .table(table)
+        .add_column(def.not_null())
+        .to_owned(),
+    )
+    .await?;
+
+  let backend = manager.get_database_backend();
+  let table_name = table.to_string();
+  let column_name = Color::ColorNew.to_string();
+  let sql = format!("UPDATE {table_name} SET {column_name} = {expr}");
+
+  manager
+    .get_connection()
+    .execute(Statement::from_string(backend, sql))
+    .await?;
+
+  manager
+    .alter_table(
+      Table::alter()
+        .table(table)
+        .drop_column(Color::Color)
+        .to_owned(),
+    )
+    .await?;
+
+  manager
+    .alter_table(
+      Table::alter()
+        .table(table)
+        .rename_column(Color::ColorNew, Color::Color)
+        .to_owned(),
+    )
+    .await
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    let expr = hex_to_int(manager.get_database_backend());
+
+    for table in [Tables::Board, Tables::Paint] {
+      let mut def = ColumnDef::new(Color::ColorNew);
+      convert(manager, table, def.integer().default(0), &expr).await?;
+    }
+
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    let expr = int_to_hex(manager.get_database_backend());
+
+    for table in [Tables::Board, Tables::Paint] {
+      let mut def = ColumnDef::new(Color::ColorNew);
+      convert(manager, table, def.string_len(7).default("#FFFFFF"), expr).await?;
+    }
+
+    Ok(())
+  }
+}
+
+#[derive(Iden, Clone, Copy)]
+enum Tables {
+  Board,
+  Paint,
+}
+
+#[derive(Iden)]
+enum Color {
+  Color,
+  ColorNew,
+}
@@ -0,0 +1,61 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_paint_x_y_time")
+          .table(Paint::Table)
+          .col(Paint::X)
+          .col(Paint::Y)
+          .col(Paint::Time)
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_paint_uid_time")
+          .table(Paint::Table)
+          .col(Paint::Uid)
+          .col(Paint::Time)
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_paint_time")
+          .table(Paint::Table)
+          .col(Paint::Time)
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    for name in ["idx_paint_x_y_time", "idx_paint_uid_time", "idx_paint_time"] {
+      manager
+        .drop_index(Index::drop().name(name).table(Paint::Table).to_owned())
+        .await?;
+    }
+
+    Ok(())
+  }
+}
+
+#[derive(Iden)]
+enum Paint {
+  Table,
+  X,
+  Y,
+  Uid,
+  Time,
+}
@@ -0,0 +1,311 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .create_table(
+        Table::create()
+          .table(Canvas::Table)
+          .if_not_exists()
+          .col(
+            ColumnDef::new(Canvas::Id)
+              .integer()
+              .not_null()
+              .auto_increment()
+              .primary_key(),
+          )
+          .col(
+            ColumnDef::new(Canvas::Name)
+              .string_len(32)
+              .not_null()
+              .unique_key(),
+          )
+          .col(ColumnDef::new(Canvas::Width).integer().not_null())
+          .col(ColumnDef::new(Canvas::Height).integer().not_null())
+          .col(ColumnDef::new(Canvas::BeginTime).time().not_null())
+          .col(ColumnDef::new(Canvas::EndTime).time().not_null())
+          .col(ColumnDef::new(Canvas::Palette).text())
+          .to_owned(),
+      )
+      .await?;
+
+    // the board that existed so far, gets id 1
+    manager
+      .exec_stmt(
+        Query::insert()
+          .into_table(Canvas::Table)
+          .columns([
+            Canvas::Name,
+            Canvas::Width,
+            Canvas::Height,
+            Canvas::BeginTime,
+            Canvas::EndTime,
+          ])
+          .values_panic([
+            "main".into(),
+            1000.into(),
+            600.into(),
+            Expr::cust("'15:00:00'"),
+            Expr::cust("'20:00:00'"),
+          ])
+          .to_owned(),
+      )
+      .await?;
+
+    // the primary key of `board` changes, so it has to be rebuilt
+    manager
+      .create_table(
+        Table::create()
+          .table(BoardNew::Table)
+          .col(ColumnDef::new(Board::CanvasId).integer().not_null())
+          .col(ColumnDef::new(Board::X).integer().not_null())
+          .col(ColumnDef::new(Board::Y).integer().not_null())
+          .col(ColumnDef::new(Board::Color).integer().not_null())
+          .col(ColumnDef::new(Board::Uid).integer().not_null())
+          .col(
+            ColumnDef::new(Board::Time)
+              .timestamp_with_time_zone()
+              .not_null(),
+          )
+          .primary_key(
+            Index::create()
+              .col(Board::CanvasId)
+              .col(Board::X)
+              .col(Board::Y),
+          )
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .exec_stmt(
+        Query::insert()
+          .into_table(BoardNew::Table)
+          .columns([
+            Board::CanvasId,
+            Board::X,
+            Board::Y,
+            Board::Color,
+            Board::Uid,
+            Board::Time,
+          ])
+          .select_from(
+            Query::select()
+              .expr(Expr::val(1))
+              .columns([Board::X, Board::Y, Board::Color, Board::Uid, Board::Time])
+              .from(Board::Table)
+              .to_owned(),
+          )
+          .unwrap()
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .drop_table(Table::drop().table(Board::Table).to_owned())
+      .await?;
+
+    manager
+      .rename_table(
+        Table::rename()
+          .table(BoardNew::Table, Board::Table)
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Paint::Table)
+          .add_column(
+            ColumnDef::new(Paint::CanvasId)
+              .integer()
+              .not_null()
+              .default(1),
+          )
+          .to_owned(),
+      )
+      .await?;
+
+    // scope the indexes to the canvas
+    for name in ["idx_paint_x_y_time", "idx_paint_time"] {
+      manager
+        .drop_index(Index::drop().name(name).table(Paint::Table).to_owned())
+        .await?;
+    }
+
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_paint_canvas_x_y_time")
+          .table(Paint::Table)
+          .col(Paint::CanvasId)
+          .col(Paint::X)
+          .col(Paint::Y)
+          .col(Paint::Time)
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_paint_canvas_time")
+          .table(Paint::Table)
+          .col(Paint::CanvasId)
+          .col(Paint::Time)
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    for name in ["idx_paint_canvas_x_y_time", "idx_paint_canvas_time"] {
+      manager
+        .drop_index(Index::drop().name(name).table(Paint::Table).to_owned())
+        .await?;
+    }
+
+    // other canvases can not be represented any more
+    manager
+      .exec_stmt(
+        Query::delete()
+          .from_table(Board::Table)
+          .and_where(Expr::col(Board::CanvasId).ne(1))
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .exec_stmt(
+        Query::delete()
+          .from_table(Paint::Table)
+          .and_where(Expr::col(Paint::CanvasId).ne(1))
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Paint::Table)
+          .drop_column(Paint::CanvasId)
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_paint_x_y_time")
+          .table(Paint::Table)
+          .col(Paint::X)
+          .col(Paint::Y)
+          .col(Paint::Time)
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_paint_time")
+          .table(Paint::Table)
+          .col(Paint::Time)
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .create_table(
+        Table::create()
+          .table(BoardNew::Table)
+          .col(ColumnDef::new(Board::X).integer().not_null())
+          .col(ColumnDef::new(Board::Y).integer().not_null())
+          .col(ColumnDef::new(Board::Color).integer().not_null())
+          .col(ColumnDef::new(Board::Uid).integer().not_null())
+          .col(
+            ColumnDef::new(Board::Time)
+              .timestamp_with_time_zone()
+              .not_null(),
+          )
+          .primary_key(Index::create().col(Board::X).col(Board::Y))
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .exec_stmt(
+        Query::insert()
+          .into_table(BoardNew::Table)
+          .columns([Board::X, Board::Y, Board::Color, Board::Uid, Board::Time])
+          .select_from(
+            Query::select()
+              .columns([Board::X, Board::Y, Board::Color, Board::Uid, Board::Time])
+              .from(Board::Table)
+              .to_owned(),
+          )
+          .unwrap()
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .drop_table(Table::drop().table(Board::Table).to_owned())
+      .await?;
+
+    manager
+      .rename_table(
+        Table::rename()
+          .table(BoardNew::Table, Board::Table)
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .drop_table(Table::drop().table(Canvas::Table).to_owned())
+      .await
+  }
+}
+
+#[derive(Iden)]
+enum Canvas {
+  Table,
+  Id,
+  Name,
+  Width,
+  Height,
+  BeginTime,
+  EndTime,
+  Palette,
+}
+
+#[derive(Iden)]
+enum Board {
+  Table,
+  CanvasId,
+  X,
+  Y,
+  Color,
+  Uid,
+  Time,
+}
+
+#[derive(Iden)]
+enum BoardNew {
+  #[iden = "board_new"]
+  Table,
+}
+
+#[derive(Iden)]
+enum Paint {
+  Table,
+  CanvasId,
+  X,
+  Y,
+  Time,
+}
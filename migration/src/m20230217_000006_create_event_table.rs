@@ -0,0 +1,77 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .create_table(
+        Table::create()
+          .table(Event::Table)
+          .if_not_exists()
+          .col(
+            ColumnDef::new(Event::Id)
+              .integer()
+              .not_null()
+              .auto_increment()
+              .primary_key(),
+          )
+          .col(
+            ColumnDef::new(Event::Name)
+              .string_len(32)
+              .not_null()
+              .unique_key(),
+          )
+          .col(
+            ColumnDef::new(Event::CanvasId)
+              .integer()
+              .not_null()
+              .unique_key(),
+          )
+          .col(
+            ColumnDef::new(Event::StartsAt)
+              .timestamp_with_time_zone()
+              .not_null(),
+          )
+          .col(
+            ColumnDef::new(Event::EndsAt)
+              .timestamp_with_time_zone()
+              .not_null(),
+          )
+          .col(ColumnDef::new(Event::Status).string_len(16).not_null())
+          .foreign_key(
+            ForeignKey::create()
+              .name("fk_event_canvas")
+              .from(Event::Table, Event::CanvasId)
+              .to(Canvas::Table, Canvas::Id),
+          )
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(Event::Table).to_owned())
+      .await
+  }
+}
+
+#[derive(Iden)]
+enum Event {
+  Table,
+  Id,
+  Name,
+  CanvasId,
+  StartsAt,
+  EndsAt,
+  Status,
+}
+
+#[derive(Iden)]
+enum Canvas {
+  Table,
+  Id,
+}
@@ -0,0 +1,61 @@
+use sea_orm_migration::{
+  prelude::*,
+  sea_orm::{ConnectionTrait, Statement},
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // the color `setup` filled the board with, white by default
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Canvas::Table)
+          .add_column(
+            ColumnDef::new(Canvas::Background)
+              .integer()
+              .not_null()
+              .default(0xffffff),
+          )
+          .to_owned(),
+      )
+      .await?;
+
+    // which is still the most common color of pixels nobody painted
+    let sql = "UPDATE canvas SET background = COALESCE((\
+        SELECT color FROM board \
+        WHERE board.canvas_id = canvas.id AND board.uid = -1 \
+        GROUP BY color ORDER BY COUNT(*) DESC LIMIT 1\
+      ), background)";
+
+    manager
+      .get_connection()
+      .execute(Statement::from_string(
+        manager.get_database_backend(),
+        sql.to_owned(),
+      ))
+      .await?;
+
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Canvas::Table)
+          .drop_column(Canvas::Background)
+          .to_owned(),
+      )
+      .await
+  }
+}
+
+#[derive(Iden)]
+enum Canvas {
+  Table,
+  Background,
+}
@@ -0,0 +1,67 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .create_table(
+        Table::create()
+          .table(Snapshot::Table)
+          .if_not_exists()
+          .col(
+            ColumnDef::new(Snapshot::Id)
+              .integer()
+              .not_null()
+              .auto_increment()
+              .primary_key(),
+          )
+          .col(ColumnDef::new(Snapshot::CanvasId).integer().not_null())
+          .col(
+            ColumnDef::new(Snapshot::Time)
+              .timestamp_with_time_zone()
+              .not_null(),
+          )
+          .col(ColumnDef::new(Snapshot::Width).integer().not_null())
+          .col(ColumnDef::new(Snapshot::Height).integer().not_null())
+          .col(
+            ColumnDef::new(Snapshot::Data)
+              .blob(BlobSize::Long)
+              .not_null(),
+          )
+          .to_owned(),
+      )
+      .await?;
+
+    // find the latest keyframe before a time
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_snapshot_canvas_time")
+          .table(Snapshot::Table)
+          .col(Snapshot::CanvasId)
+          .col(Snapshot::Time)
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(Snapshot::Table).to_owned())
+      .await
+  }
+}
+
+#[derive(Iden)]
+enum Snapshot {
+  Table,
+  Id,
+  CanvasId,
+  Time,
+  Width,
+  Height,
+  Data,
+}
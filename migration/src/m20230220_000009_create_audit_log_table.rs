@@ -0,0 +1,49 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .create_table(
+        Table::create()
+          .table(AuditLog::Table)
+          .if_not_exists()
+          .col(
+            ColumnDef::new(AuditLog::Id)
+              .integer()
+              .not_null()
+              .auto_increment()
+              .primary_key(),
+          )
+          .col(ColumnDef::new(AuditLog::Actor).integer().not_null())
+          .col(ColumnDef::new(AuditLog::Action).string_len(32).not_null())
+          .col(ColumnDef::new(AuditLog::Params).text().not_null())
+          .col(
+            ColumnDef::new(AuditLog::Time)
+              .timestamp_with_time_zone()
+              .not_null(),
+          )
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(AuditLog::Table).to_owned())
+      .await
+  }
+}
+
+#[derive(Iden)]
+enum AuditLog {
+  Table,
+  Id,
+  Actor,
+  Action,
+  Params,
+  Time,
+}
@@ -0,0 +1,71 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .create_table(
+        Table::create()
+          .table(Region::Table)
+          .if_not_exists()
+          .col(
+            ColumnDef::new(Region::Id)
+              .integer()
+              .not_null()
+              .auto_increment()
+              .primary_key(),
+          )
+          .col(ColumnDef::new(Region::CanvasId).integer().not_null())
+          .col(ColumnDef::new(Region::Name).string_len(32).not_null())
+          .col(ColumnDef::new(Region::X0).integer().not_null())
+          .col(ColumnDef::new(Region::Y0).integer().not_null())
+          .col(ColumnDef::new(Region::X1).integer().not_null())
+          .col(ColumnDef::new(Region::Y1).integer().not_null())
+          .col(ColumnDef::new(Region::Mask).blob(BlobSize::Long))
+          .col(ColumnDef::new(Region::Roles).text())
+          .col(ColumnDef::new(Region::Uids).text())
+          .col(ColumnDef::new(Region::StartsAt).timestamp_with_time_zone())
+          .col(ColumnDef::new(Region::EndsAt).timestamp_with_time_zone())
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_region_canvas_name")
+          .table(Region::Table)
+          .col(Region::CanvasId)
+          .col(Region::Name)
+          .unique()
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(Region::Table).to_owned())
+      .await
+  }
+}
+
+#[derive(Iden)]
+enum Region {
+  Table,
+  Id,
+  CanvasId,
+  Name,
+  X0,
+  Y0,
+  X1,
+  Y1,
+  Mask,
+  Roles,
+  Uids,
+  StartsAt,
+  EndsAt,
+}
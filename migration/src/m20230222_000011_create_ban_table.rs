@@ -0,0 +1,43 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .create_table(
+        Table::create()
+          .table(Ban::Table)
+          .if_not_exists()
+          .col(ColumnDef::new(Ban::Uid).integer().not_null().primary_key())
+          .col(ColumnDef::new(Ban::Reason).text())
+          .col(ColumnDef::new(Ban::Actor).integer().not_null())
+          .col(
+            ColumnDef::new(Ban::Time)
+              .timestamp_with_time_zone()
+              .not_null(),
+          )
+          .col(ColumnDef::new(Ban::Until).timestamp_with_time_zone())
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(Ban::Table).to_owned())
+      .await
+  }
+}
+
+#[derive(Iden)]
+enum Ban {
+  Table,
+  Uid,
+  Reason,
+  Actor,
+  Time,
+  Until,
+}
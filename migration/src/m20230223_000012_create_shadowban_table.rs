@@ -0,0 +1,46 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .create_table(
+        Table::create()
+          .table(Shadowban::Table)
+          .if_not_exists()
+          .col(
+            ColumnDef::new(Shadowban::Uid)
+              .integer()
+              .not_null()
+              .primary_key(),
+          )
+          .col(ColumnDef::new(Shadowban::Reason).text())
+          .col(ColumnDef::new(Shadowban::Actor).integer().not_null())
+          .col(
+            ColumnDef::new(Shadowban::Time)
+              .timestamp_with_time_zone()
+              .not_null(),
+          )
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(Shadowban::Table).to_owned())
+      .await
+  }
+}
+
+#[derive(Iden)]
+enum Shadowban {
+  Table,
+  Uid,
+  Reason,
+  Actor,
+  Time,
+}
@@ -0,0 +1,64 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .create_table(
+        Table::create()
+          .table(ShadowPaint::Table)
+          .if_not_exists()
+          .col(
+            ColumnDef::new(ShadowPaint::Id)
+              .integer()
+              .not_null()
+              .auto_increment()
+              .primary_key(),
+          )
+          .col(ColumnDef::new(ShadowPaint::CanvasId).integer().not_null())
+          .col(ColumnDef::new(ShadowPaint::X).integer().not_null())
+          .col(ColumnDef::new(ShadowPaint::Y).integer().not_null())
+          .col(ColumnDef::new(ShadowPaint::Color).integer().not_null())
+          .col(ColumnDef::new(ShadowPaint::Uid).integer().not_null())
+          .col(
+            ColumnDef::new(ShadowPaint::Time)
+              .timestamp_with_time_zone()
+              .not_null(),
+          )
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_shadow_paint_uid_time")
+          .table(ShadowPaint::Table)
+          .col(ShadowPaint::Uid)
+          .col(ShadowPaint::Time)
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(ShadowPaint::Table).to_owned())
+      .await
+  }
+}
+
+#[derive(Iden)]
+enum ShadowPaint {
+  Table,
+  Id,
+  CanvasId,
+  X,
+  Y,
+  Color,
+  Uid,
+  Time,
+}
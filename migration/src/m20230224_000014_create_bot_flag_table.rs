@@ -0,0 +1,53 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .create_table(
+        Table::create()
+          .table(BotFlag::Table)
+          .if_not_exists()
+          .col(
+            ColumnDef::new(BotFlag::Id)
+              .integer()
+              .not_null()
+              .auto_increment()
+              .primary_key(),
+          )
+          .col(ColumnDef::new(BotFlag::Uid).integer().not_null())
+          .col(ColumnDef::new(BotFlag::CanvasId).integer().not_null())
+          .col(ColumnDef::new(BotFlag::Source).string_len(16).not_null())
+          .col(ColumnDef::new(BotFlag::Score).double().not_null())
+          .col(ColumnDef::new(BotFlag::Details).text().not_null())
+          .col(
+            ColumnDef::new(BotFlag::Time)
+              .timestamp_with_time_zone()
+              .not_null(),
+          )
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(BotFlag::Table).to_owned())
+      .await
+  }
+}
+
+#[derive(Iden)]
+enum BotFlag {
+  Table,
+  Id,
+  Uid,
+  CanvasId,
+  Source,
+  Score,
+  Details,
+  Time,
+}
@@ -0,0 +1,59 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .create_table(
+        Table::create()
+          .table(SessionLog::Table)
+          .if_not_exists()
+          .col(
+            ColumnDef::new(SessionLog::Id)
+              .integer()
+              .not_null()
+              .auto_increment()
+              .primary_key(),
+          )
+          .col(ColumnDef::new(SessionLog::Uid).integer().not_null())
+          .col(ColumnDef::new(SessionLog::CanvasId).integer().not_null())
+          .col(ColumnDef::new(SessionLog::Ip).string_len(64).not_null())
+          .col(
+            ColumnDef::new(SessionLog::Time)
+              .timestamp_with_time_zone()
+              .not_null(),
+          )
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_session_log_uid")
+          .table(SessionLog::Table)
+          .col(SessionLog::Uid)
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(SessionLog::Table).to_owned())
+      .await
+  }
+}
+
+#[derive(Iden)]
+enum SessionLog {
+  Table,
+  Id,
+  Uid,
+  CanvasId,
+  Ip,
+  Time,
+}
@@ -0,0 +1,41 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // all paints so far were made by their users
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Paint::Table)
+          .add_column(
+            ColumnDef::new(Paint::Kind)
+              .string_len(16)
+              .not_null()
+              .default("paint"),
+          )
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Paint::Table)
+          .drop_column(Paint::Kind)
+          .to_owned(),
+      )
+      .await
+  }
+}
+
+#[derive(Iden)]
+enum Paint {
+  Table,
+  Kind,
+}
@@ -0,0 +1,563 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, Json};
+use chrono::Local;
+use sea_orm::{sea_query::OnConflict, ActiveValue, EntityTrait, QueryOrder, QuerySelect};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  audit::audit,
+  auth::Admin,
+  history::parse_time,
+  moderation::{restore_rect, rollback},
+  session::{Notice, SessionInfo},
+  AppState,
+};
+use yur_paintboard::{
+  board::grow_board,
+  entities::{ban, bot_flag, prelude::*, shadowban},
+  query::Rect,
+};
+
+// TODO(config)
+const MAX_ANNOUNCEMENT_LEN: usize = 1024;
+// TODO(config)
+const MAX_FLAGS: u64 = 100;
+// TODO(config)
+/// Every pixel is kept in memory, so canvases may not grow past this.
+const MAX_PIXELS: usize = 4_000_000;
+
+#[derive(Deserialize)]
+pub struct RollbackRequest {
+  /// The canvas served on `/ws` if not set.
+  canvas: Option<String>,
+  uid: i32,
+  /// RFC 3339
+  since: Option<String>,
+  /// RFC 3339
+  until: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct RollbackResponse {
+  restored: usize,
+}
+
+/// `POST /admin/rollback`, undo the pixels currently owned by a user.
+pub async fn admin_rollback(
+  State(state): State<Arc<AppState>>,
+  admin: Admin,
+  Json(req): Json<RollbackRequest>,
+) -> Result<Json<RollbackResponse>, StatusCode> {
+  let canvas_name = req.canvas.as_ref().unwrap_or(&state.default_canvas);
+  let canvas = state
+    .canvases
+    .get(canvas_name)
+    .ok_or(StatusCode::NOT_FOUND)?;
+
+  let since = parse_time(&req.since)?;
+  let until = parse_time(&req.until)?;
+
+  let restored = rollback(&state, canvas, req.uid, since, until, admin.uid)
+    .await
+    .map_err(|err| {
+      tracing::error!("Rollback failed: {err}");
+      StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+  let params = serde_json::json!({
+    "canvas": canvas.name,
+    "uid": req.uid,
+    "since": req.since,
+    "until": req.until,
+    "restored": restored,
+  });
+
+  if let Err(err) = audit(&state.db, admin.uid, "rollback", params).await {
+    tracing::error!("Write audit log failed: {err}");
+  }
+
+  Ok(Json(RollbackResponse { restored }))
+}
+
+#[derive(Deserialize)]
+pub struct RestoreRequest {
+  /// The canvas served on `/ws` if not set.
+  canvas: Option<String>,
+  x0: u16,
+  y0: u16,
+  x1: u16,
+  y1: u16,
+  /// RFC 3339
+  time: String,
+}
+
+#[derive(Serialize)]
+pub struct RestoreResponse {
+  restored: usize,
+}
+
+/// `POST /admin/restore`, restore a rectangle `x0..x1` × `y0..y1` to
+/// how it looked at `time`.
+pub async fn admin_restore(
+  State(state): State<Arc<AppState>>,
+  admin: Admin,
+  Json(req): Json<RestoreRequest>,
+) -> Result<Json<RestoreResponse>, StatusCode> {
+  let canvas_name = req.canvas.as_ref().unwrap_or(&state.default_canvas);
+  let canvas = state
+    .canvases
+    .get(canvas_name)
+    .ok_or(StatusCode::NOT_FOUND)?;
+
+  if req.x0 >= req.x1 || req.y0 >= req.y1 {
+    return Err(StatusCode::BAD_REQUEST);
+  }
+
+  let rect = Rect {
+    x0: req.x0,
+    y0: req.y0,
+    x1: req.x1,
+    y1: req.y1,
+  };
+
+  let time = parse_time(&Some(req.time.clone()))?.unwrap();
+
+  let restored = restore_rect(&state, canvas, rect, time, admin.uid)
+    .await
+    .map_err(|err| {
+      tracing::error!("Restore failed: {err}");
+      StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+  let params = serde_json::json!({
+    "canvas": canvas.name,
+    "x0": req.x0,
+    "y0": req.y0,
+    "x1": req.x1,
+    "y1": req.y1,
+    "time": req.time,
+    "restored": restored,
+  });
+
+  if let Err(err) = audit(&state.db, admin.uid, "restore", params).await {
+    tracing::error!("Write audit log failed: {err}");
+  }
+
+  Ok(Json(RestoreResponse { restored }))
+}
+
+#[derive(Deserialize)]
+pub struct BanRequest {
+  uid: i32,
+  reason: Option<String>,
+  /// RFC 3339, forever if not set.
+  until: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct BanResponse {
+  /// Live sessions of the user that were closed.
+  kicked: usize,
+}
+
+/// `POST /admin/ban`, stop a user from painting and close their sessions.
+pub async fn admin_ban(
+  State(state): State<Arc<AppState>>,
+  admin: Admin,
+  Json(req): Json<BanRequest>,
+) -> Result<Json<BanResponse>, StatusCode> {
+  let until = parse_time(&req.until)?;
+  let now = Local::now();
+
+  let ban = ban::ActiveModel {
+    uid: ActiveValue::set(req.uid),
+    reason: ActiveValue::set(req.reason.clone()),
+    actor: ActiveValue::set(admin.uid),
+    time: ActiveValue::set(now),
+    until: ActiveValue::set(until),
+  };
+
+  Ban::insert(ban)
+    .on_conflict(
+      OnConflict::column(ban::Column::Uid)
+        .update_columns([
+          ban::Column::Reason,
+          ban::Column::Actor,
+          ban::Column::Time,
+          ban::Column::Until,
+        ])
+        .to_owned(),
+    )
+    .exec(&state.db)
+    .await
+    .map_err(|err| {
+      tracing::error!("Save ban failed: {err}");
+      StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+  let ban = ban::Model {
+    uid: req.uid,
+    reason: req.reason.clone(),
+    actor: admin.uid,
+    time: now,
+    until,
+  };
+
+  state.bans.lock().insert(req.uid, ban);
+
+  let kicked = state.sessions.count_of(req.uid);
+
+  // fails only if nobody is listening
+  let _ = state.notices.send(Notice::Kick(req.uid));
+
+  let params = serde_json::json!({
+    "uid": req.uid,
+    "reason": req.reason,
+    "until": req.until,
+    "kicked": kicked,
+  });
+
+  if let Err(err) = audit(&state.db, admin.uid, "ban", params).await {
+    tracing::error!("Write audit log failed: {err}");
+  }
+
+  Ok(Json(BanResponse { kicked }))
+}
+
+#[derive(Deserialize)]
+pub struct UnbanRequest {
+  uid: i32,
+}
+
+/// `POST /admin/unban`, lift the ban of a user.
+pub async fn admin_unban(
+  State(state): State<Arc<AppState>>,
+  admin: Admin,
+  Json(req): Json<UnbanRequest>,
+) -> Result<StatusCode, StatusCode> {
+  let res = Ban::delete_by_id(req.uid)
+    .exec(&state.db)
+    .await
+    .map_err(|err| {
+      tracing::error!("Delete ban failed: {err}");
+      StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+  state.bans.lock().remove(&req.uid);
+
+  if res.rows_affected == 0 {
+    return Err(StatusCode::NOT_FOUND);
+  }
+
+  let params = serde_json::json!({ "uid": req.uid });
+
+  if let Err(err) = audit(&state.db, admin.uid, "unban", params).await {
+    tracing::error!("Write audit log failed: {err}");
+  }
+
+  Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+pub struct AnnounceRequest {
+  /// All canvases if not set.
+  canvas: Option<String>,
+  message: String,
+}
+
+/// `POST /admin/announce`, show a message to connected clients.
+pub async fn admin_announce(
+  State(state): State<Arc<AppState>>,
+  admin: Admin,
+  Json(req): Json<AnnounceRequest>,
+) -> Result<StatusCode, StatusCode> {
+  if let Some(canvas) = &req.canvas {
+    if !state.canvases.contains_key(canvas) {
+      return Err(StatusCode::NOT_FOUND);
+    }
+  }
+
+  if req.message.is_empty() || req.message.len() > MAX_ANNOUNCEMENT_LEN {
+    return Err(StatusCode::BAD_REQUEST);
+  }
+
+  let params = serde_json::json!({
+    "canvas": req.canvas,
+    "message": req.message,
+  });
+
+  let notice = Notice::Announce {
+    canvas: req.canvas,
+    message: req.message,
+  };
+
+  // fails only if nobody is listening
+  let _ = state.notices.send(notice);
+
+  if let Err(err) = audit(&state.db, admin.uid, "announce", params).await {
+    tracing::error!("Write audit log failed: {err}");
+  }
+
+  Ok(StatusCode::NO_CONTENT)
+}
+
+/// `GET /admin/sessions`, list live websockets.
+pub async fn admin_sessions(
+  State(state): State<Arc<AppState>>,
+  admin: Admin,
+) -> Json<Vec<SessionInfo>> {
+  let sessions = state.sessions.list();
+
+  let params = serde_json::json!({ "count": sessions.len() });
+
+  if let Err(err) = audit(&state.db, admin.uid, "sessions", params).await {
+    tracing::error!("Write audit log failed: {err}");
+  }
+
+  Json(sessions)
+}
+
+#[derive(Deserialize)]
+pub struct FreezeRequest {
+  frozen: bool,
+}
+
+/// `POST /admin/freeze`, stop or resume painting on all canvases
+/// without dropping connections.
+pub async fn admin_freeze(
+  State(state): State<Arc<AppState>>,
+  admin: Admin,
+  Json(req): Json<FreezeRequest>,
+) -> StatusCode {
+  let changed = state.set_frozen(req.frozen);
+
+  let params = serde_json::json!({
+    "frozen": req.frozen,
+    "changed": changed,
+  });
+
+  if let Err(err) = audit(&state.db, admin.uid, "freeze", params).await {
+    tracing::error!("Write audit log failed: {err}");
+  }
+
+  StatusCode::NO_CONTENT
+}
+
+#[derive(Deserialize)]
+pub struct ShadowbanRequest {
+  uid: i32,
+  reason: Option<String>,
+}
+
+/// `POST /admin/shadowban`, keep the paints of a user to themselves
+/// without telling them.
+pub async fn admin_shadowban(
+  State(state): State<Arc<AppState>>,
+  admin: Admin,
+  Json(req): Json<ShadowbanRequest>,
+) -> Result<StatusCode, StatusCode> {
+  let shadowban = shadowban::ActiveModel {
+    uid: ActiveValue::set(req.uid),
+    reason: ActiveValue::set(req.reason.clone()),
+    actor: ActiveValue::set(admin.uid),
+    time: ActiveValue::set(Local::now()),
+  };
+
+  Shadowban::insert(shadowban)
+    .on_conflict(
+      OnConflict::column(shadowban::Column::Uid)
+        .update_columns([
+          shadowban::Column::Reason,
+          shadowban::Column::Actor,
+          shadowban::Column::Time,
+        ])
+        .to_owned(),
+    )
+    .exec(&state.db)
+    .await
+    .map_err(|err| {
+      tracing::error!("Save shadowban failed: {err}");
+      StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+  state.shadowbans.lock().insert(req.uid);
+
+  let params = serde_json::json!({
+    "uid": req.uid,
+    "reason": req.reason,
+  });
+
+  if let Err(err) = audit(&state.db, admin.uid, "shadowban", params).await {
+    tracing::error!("Write audit log failed: {err}");
+  }
+
+  Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+pub struct UnshadowbanRequest {
+  uid: i32,
+}
+
+/// `POST /admin/unshadowban`, apply the paints of a user again.
+pub async fn admin_unshadowban(
+  State(state): State<Arc<AppState>>,
+  admin: Admin,
+  Json(req): Json<UnshadowbanRequest>,
+) -> Result<StatusCode, StatusCode> {
+  let res = Shadowban::delete_by_id(req.uid)
+    .exec(&state.db)
+    .await
+    .map_err(|err| {
+      tracing::error!("Delete shadowban failed: {err}");
+      StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+  state.shadowbans.lock().remove(&req.uid);
+
+  if res.rows_affected == 0 {
+    return Err(StatusCode::NOT_FOUND);
+  }
+
+  let params = serde_json::json!({ "uid": req.uid });
+
+  if let Err(err) = audit(&state.db, admin.uid, "unshadowban", params).await {
+    tracing::error!("Write audit log failed: {err}");
+  }
+
+  Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Serialize)]
+pub struct FlagInfo {
+  id: i32,
+  uid: i32,
+  canvas_id: i32,
+  source: String,
+  score: f64,
+  details: serde_json::Value,
+  time: String,
+}
+
+/// `GET /admin/flags`, the latest users flagged by the bot detection.
+pub async fn admin_flags(
+  State(state): State<Arc<AppState>>,
+  admin: Admin,
+) -> Result<Json<Vec<FlagInfo>>, StatusCode> {
+  let flags = BotFlag::find()
+    .order_by_desc(bot_flag::Column::Id)
+    .limit(MAX_FLAGS)
+    .all(&state.db)
+    .await
+    .map_err(|err| {
+      tracing::error!("Fetch bot flags failed: {err}");
+      StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+  let params = serde_json::json!({ "count": flags.len() });
+
+  if let Err(err) = audit(&state.db, admin.uid, "flags", params).await {
+    tracing::error!("Write audit log failed: {err}");
+  }
+
+  let flags = flags
+    .into_iter()
+    .map(|flag| FlagInfo {
+      id: flag.id,
+      uid: flag.uid,
+      canvas_id: flag.canvas_id,
+      source: flag.source,
+      score: flag.score,
+      details: serde_json::from_str(&flag.details).unwrap_or_default(),
+      time: flag.time.to_rfc3339(),
+    })
+    .collect();
+
+  Ok(Json(flags))
+}
+
+#[derive(Deserialize)]
+pub struct ResizeRequest {
+  /// The canvas served on `/ws` if not set.
+  canvas: Option<String>,
+  width: u16,
+  height: u16,
+}
+
+/// `POST /admin/resize`, grow a canvas without a restart. The new area
+/// is painted with the background.
+pub async fn admin_resize(
+  State(state): State<Arc<AppState>>,
+  admin: Admin,
+  Json(req): Json<ResizeRequest>,
+) -> Result<StatusCode, StatusCode> {
+  let canvas_name = req.canvas.as_ref().unwrap_or(&state.default_canvas);
+  let canvas = state
+    .canvases
+    .get(canvas_name)
+    .ok_or(StatusCode::NOT_FOUND)?;
+
+  let now = Local::now();
+
+  // the board of a closed canvas is final
+  if canvas.is_closed(now) {
+    return Err(StatusCode::BAD_REQUEST);
+  }
+
+  if req.width as usize * req.height as usize > MAX_PIXELS {
+    return Err(StatusCode::BAD_REQUEST);
+  }
+
+  let _resizing = state.resizing.lock().await;
+
+  let (old_width, old_height) = (canvas.width(), canvas.height());
+
+  // shrinking would drop paints
+  if req.width < old_width || req.height < old_height {
+    return Err(StatusCode::BAD_REQUEST);
+  }
+
+  if req.width == old_width && req.height == old_height {
+    return Ok(StatusCode::NO_CONTENT);
+  }
+
+  let model = Canvas::find_by_id(canvas.id)
+    .one(&state.db)
+    .await
+    .ok()
+    .flatten()
+    .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+  grow_board(&state.db, &model, req.width.into(), req.height.into(), now)
+    .await
+    .map_err(|err| {
+      tracing::error!("Grow board failed: {err}");
+      StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+  canvas.grow(req.width, req.height, now);
+
+  let notice = Notice::Resize {
+    canvas: canvas.name.clone(),
+    width: req.width,
+    height: req.height,
+  };
+
+  // fails only if nobody is listening
+  let _ = state.notices.send(notice);
+
+  let params = serde_json::json!({
+    "canvas": canvas.name,
+    "width": req.width,
+    "height": req.height,
+    "old_width": old_width,
+    "old_height": old_height,
+  });
+
+  if let Err(err) = audit(&state.db, admin.uid, "resize", params).await {
+    tracing::error!("Write audit log failed: {err}");
+  }
+
+  Ok(StatusCode::NO_CONTENT)
+}
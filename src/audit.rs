@@ -0,0 +1,26 @@
+use chrono::Local;
+use sea_orm::{ActiveModelTrait, ActiveValue, DatabaseConnection, DbErr};
+
+use yur_paintboard::entities::audit_log;
+
+/// Record a moderation action taken by `actor`.
+pub async fn audit(
+  db: &DatabaseConnection,
+  actor: i32,
+  action: &str,
+  params: serde_json::Value,
+) -> Result<(), DbErr> {
+  tracing::info!(actor, action, %params, "Audit");
+
+  let log = audit_log::ActiveModel {
+    actor: ActiveValue::set(actor),
+    action: ActiveValue::set(action.to_owned()),
+    params: ActiveValue::set(params.to_string()),
+    time: ActiveValue::set(Local::now()),
+    ..Default::default()
+  };
+
+  log.insert(db).await?;
+
+  Ok(())
+}
@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use axum::{
+  async_trait,
+  extract::FromRequestParts,
+  http::{header::AUTHORIZATION, request::Parts, StatusCode},
+};
+use jsonwebtoken::{decode, Algorithm, Validation};
+use serde::Deserialize;
+
+use crate::AppState;
+
+#[derive(Deserialize)]
+pub struct Claims {
+  #[allow(dead_code)]
+  exp: usize,
+  pub uid: i32,
+  #[serde(default)]
+  pub role: Option<String>,
+  /// Unix time the user last solved a captcha at the SSO.
+  #[serde(default)]
+  pub captcha: Option<i64>,
+}
+
+pub fn decode_token(state: &AppState, raw_token: &str) -> Option<Claims> {
+  let token = decode::<Claims>(raw_token, &state.pubkey, &Validation::new(Algorithm::EdDSA));
+
+  match token {
+    Ok(token) => Some(token.claims),
+    Err(err) => {
+      tracing::warn!(token = raw_token, "Invalid token: {err}");
+      None
+    }
+  }
+}
+
+/// A request with `Authorization: Bearer <token>` of a user with the
+/// admin role.
+pub struct Admin {
+  pub uid: i32,
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for Admin {
+  type Rejection = StatusCode;
+
+  async fn from_request_parts(
+    parts: &mut Parts,
+    state: &Arc<AppState>,
+  ) -> Result<Self, Self::Rejection> {
+    let raw_token = parts
+      .headers
+      .get(AUTHORIZATION)
+      .and_then(|value| value.to_str().ok())
+      .and_then(|value| value.strip_prefix("Bearer "))
+      .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let claims = decode_token(state, raw_token).ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if claims.role.as_deref() != Some("admin") {
+      tracing::warn!(uid = claims.uid, "Admin request without admin role!");
+      return Err(StatusCode::FORBIDDEN);
+    }
+
+    Ok(Admin { uid: claims.uid })
+  }
+}
@@ -0,0 +1,392 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use chrono::{DateTime, Local};
+use clap::Parser;
+use futures::TryStreamExt;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde::Serialize;
+
+use yur_paintboard::{
+  db::{CanvasArgs, DbArgs},
+  entities::{paint::PaintKind, prelude::*, session_log},
+  query::{stream_paints, PaintFilter},
+};
+
+// TODO(config)
+/// Users with fewer paints are left out.
+const MIN_PAINTS: usize = 20;
+/// Seconds per bin when comparing paint timing.
+const TIMING_BIN: i64 = 10;
+/// Side of the blocks users must share to be compared.
+const BLOCK: i32 = 16;
+/// Blocks painted by more users are too crowded to tell anything.
+const MAX_BLOCK_USERS: usize = 200;
+/// IPs shared by more users are NATs or proxies, likewise.
+const MAX_IP_USERS: usize = 20;
+
+#[derive(Parser)]
+#[command(name = "coordination")]
+#[command(author = "yurzhang")]
+#[command(about = "Find groups of users painting together from one script.")]
+#[command(version, long_about = None)]
+struct Args {
+  /// Only paints since this time, RFC 3339
+  #[arg(long)]
+  since: Option<DateTime<Local>>,
+
+  /// Only paints before this time, RFC 3339
+  #[arg(long)]
+  until: Option<DateTime<Local>>,
+
+  /// Pairs scoring at least this are put in the same group
+  #[arg(long, default_value_t = 0.5)]
+  min_score: f64,
+
+  /// Print the report as JSON
+  #[arg(long)]
+  json: bool,
+
+  #[command(flatten)]
+  canvas: CanvasArgs,
+
+  #[command(flatten)]
+  db: DbArgs,
+}
+
+#[derive(Default)]
+struct User {
+  paints: usize,
+  /// Paints per [`TIMING_BIN`].
+  bins: HashMap<i64, f64>,
+  pixels: HashSet<(i32, i32)>,
+  ips: HashSet<String>,
+}
+
+impl User {
+  fn norm(&self) -> f64 {
+    self.bins.values().map(|num| num * num).sum::<f64>().sqrt()
+  }
+}
+
+#[derive(Clone, Serialize)]
+struct Pair {
+  a: i32,
+  b: i32,
+  /// Cosine similarity of paint counts over time.
+  timing: f64,
+  /// Jaccard index of the IPs connected from.
+  ip: f64,
+  /// How much the pixels lie side by side without overlapping.
+  coverage: f64,
+  score: f64,
+}
+
+#[derive(Serialize)]
+struct Group {
+  rank: usize,
+  /// Mean score of the linked pairs.
+  score: f64,
+  uids: Vec<i32>,
+  shared_ips: Vec<String>,
+  pairs: Vec<Pair>,
+}
+
+fn timing(a: &User, b: &User) -> f64 {
+  let (small, large) = if a.bins.len() < b.bins.len() {
+    (a, b)
+  } else {
+    (b, a)
+  };
+
+  let dot = small
+    .bins
+    .iter()
+    .filter_map(|(bin, num)| large.bins.get(bin).map(|other| num * other))
+    .sum::<f64>();
+
+  dot / (a.norm() * b.norm())
+}
+
+fn ip(a: &User, b: &User) -> f64 {
+  let union = a.ips.union(&b.ips).count();
+
+  if union == 0 {
+    return 0.0;
+  }
+
+  a.ips.intersection(&b.ips).count() as f64 / union as f64
+}
+
+/// Share of `a`'s pixels next to one of `b`'s.
+fn near(a: &User, b: &User) -> f64 {
+  let num = a
+    .pixels
+    .iter()
+    .filter(|(x, y)| {
+      [(1, 0), (-1, 0), (0, 1), (0, -1)]
+        .iter()
+        .any(|(dx, dy)| b.pixels.contains(&(x + dx, y + dy)))
+    })
+    .count();
+
+  num as f64 / a.pixels.len() as f64
+}
+
+fn coverage(a: &User, b: &User) -> f64 {
+  let overlap =
+    a.pixels.intersection(&b.pixels).count() as f64 / a.pixels.len().min(b.pixels.len()) as f64;
+
+  near(a, b).min(near(b, a)) * (1.0 - overlap)
+}
+
+fn compare(uid_a: i32, a: &User, uid_b: i32, b: &User) -> Pair {
+  let timing = timing(a, b);
+  let ip = ip(a, b);
+  let coverage = coverage(a, b);
+
+  Pair {
+    a: uid_a,
+    b: uid_b,
+    timing,
+    ip,
+    coverage,
+    // TODO(config)
+    score: 0.4 * timing + 0.3 * ip + 0.3 * coverage,
+  }
+}
+
+/// Forget IPs shared by more than [`MAX_IP_USERS`], so that they neither
+/// make pairs to compare nor add to their score.
+fn drop_crowded_ips(users: &mut HashMap<i32, User>) {
+  let mut num = HashMap::<String, usize>::new();
+
+  for user in users.values() {
+    for ip in &user.ips {
+      *num.entry(ip.clone()).or_default() += 1;
+    }
+  }
+
+  for user in users.values_mut() {
+    user.ips.retain(|ip| num[ip] <= MAX_IP_USERS);
+  }
+}
+
+/// Pairs of users painting the same blocks or sharing an IP, the only
+/// ones worth comparing.
+fn candidates(users: &HashMap<i32, User>) -> HashSet<(i32, i32)> {
+  let mut blocks = HashMap::<(i32, i32), BTreeSet<i32>>::new();
+  let mut ips = HashMap::<&str, BTreeSet<i32>>::new();
+
+  for (uid, user) in users {
+    for (x, y) in &user.pixels {
+      blocks
+        .entry((x / BLOCK, y / BLOCK))
+        .or_default()
+        .insert(*uid);
+    }
+
+    for ip in &user.ips {
+      ips.entry(ip).or_default().insert(*uid);
+    }
+  }
+
+  let mut res = HashSet::new();
+
+  let groups = blocks
+    .into_values()
+    .filter(|uids| uids.len() <= MAX_BLOCK_USERS)
+    .chain(ips.into_values());
+
+  for uids in groups {
+    let uids = uids.into_iter().collect::<Vec<_>>();
+
+    for (idx, a) in uids.iter().enumerate() {
+      for b in &uids[idx + 1..] {
+        res.insert((*a, *b));
+      }
+    }
+  }
+
+  res
+}
+
+fn find(parent: &mut HashMap<i32, i32>, uid: i32) -> i32 {
+  let next = *parent.entry(uid).or_insert(uid);
+
+  if next == uid {
+    return uid;
+  }
+
+  let root = find(parent, next);
+  parent.insert(uid, root);
+  root
+}
+
+/// Join users linked by `pairs` into groups, best first.
+fn group(users: &HashMap<i32, User>, pairs: Vec<Pair>) -> Vec<Group> {
+  let mut parent = HashMap::new();
+
+  for pair in &pairs {
+    let a = find(&mut parent, pair.a);
+    let b = find(&mut parent, pair.b);
+    parent.insert(a, b);
+  }
+
+  let mut groups = HashMap::<i32, Vec<Pair>>::new();
+
+  for pair in pairs {
+    let root = find(&mut parent, pair.a);
+    groups.entry(root).or_default().push(pair);
+  }
+
+  let mut groups = groups
+    .into_values()
+    .map(|mut pairs| {
+      pairs.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+      let uids = pairs
+        .iter()
+        .flat_map(|pair| [pair.a, pair.b])
+        .collect::<BTreeSet<_>>();
+
+      let mut ips = HashMap::<&str, usize>::new();
+
+      for uid in &uids {
+        for ip in &users[uid].ips {
+          *ips.entry(ip).or_default() += 1;
+        }
+      }
+
+      let mut shared_ips = ips
+        .into_iter()
+        .filter(|(_, num)| *num > 1)
+        .map(|(ip, _)| ip.to_owned())
+        .collect::<Vec<_>>();
+      shared_ips.sort();
+
+      Group {
+        rank: 0,
+        score: pairs.iter().map(|pair| pair.score).sum::<f64>() / pairs.len() as f64,
+        uids: uids.into_iter().collect(),
+        shared_ips,
+        pairs,
+      }
+    })
+    .collect::<Vec<_>>();
+
+  groups.sort_by(|a, b| {
+    b.score
+      .total_cmp(&a.score)
+      .then(b.uids.len().cmp(&a.uids.len()))
+  });
+
+  for (idx, group) in groups.iter_mut().enumerate() {
+    group.rank = idx + 1;
+  }
+
+  groups
+}
+
+fn print_text(groups: &[Group]) {
+  println!("{} groups found:", groups.len());
+
+  for group in groups {
+    let uids = group
+      .uids
+      .iter()
+      .map(|uid| uid.to_string())
+      .collect::<Vec<_>>()
+      .join(", ");
+
+    println!(
+      "\n#{} score {:.2}, {} users: {uids}",
+      group.rank,
+      group.score,
+      group.uids.len(),
+    );
+
+    if !group.shared_ips.is_empty() {
+      println!("  Shared IPs: {}", group.shared_ips.join(", "));
+    }
+
+    for pair in &group.pairs {
+      println!(
+        "  {} ~ {}: {:.2} (timing {:.2}, IP {:.2}, coverage {:.2})",
+        pair.a, pair.b, pair.score, pair.timing, pair.ip, pair.coverage,
+      );
+    }
+  }
+}
+
+#[tokio::main]
+async fn main() {
+  let args = Args::parse();
+
+  let db = args.db.connect().await;
+
+  let canvas = args.canvas.find(&db).await;
+
+  let filter = PaintFilter {
+    canvas_id: Some(canvas.id),
+    kind: Some(PaintKind::Paint),
+    since: args.since,
+    until: args.until,
+    ..Default::default()
+  };
+
+  let mut users = HashMap::<i32, User>::new();
+
+  {
+    let paints = stream_paints(&db, &filter)
+      .await
+      .expect("Error fetching paints!");
+    futures::pin_mut!(paints);
+
+    while let Some(paint) = paints.try_next().await.expect("Error fetching paints!") {
+      let user = users.entry(paint.uid).or_default();
+
+      user.paints += 1;
+      *user
+        .bins
+        .entry(paint.time.timestamp() / TIMING_BIN)
+        .or_default() += 1.0;
+      user.pixels.insert((paint.x, paint.y));
+    }
+  }
+
+  users.retain(|_, user| user.paints >= MIN_PAINTS);
+
+  let mut logs = SessionLog::find().filter(session_log::Column::CanvasId.eq(canvas.id));
+
+  if let Some(since) = args.since {
+    logs = logs.filter(session_log::Column::Time.gte(since));
+  }
+
+  if let Some(until) = args.until {
+    logs = logs.filter(session_log::Column::Time.lt(until));
+  }
+
+  let logs = logs.all(&db).await.expect("Error fetching session logs!");
+
+  for log in logs {
+    if let Some(user) = users.get_mut(&log.uid) {
+      user.ips.insert(log.ip);
+    }
+  }
+
+  drop_crowded_ips(&mut users);
+
+  let pairs = candidates(&users)
+    .into_iter()
+    .map(|(a, b)| compare(a, &users[&a], b, &users[&b]))
+    .filter(|pair| pair.score >= args.min_score)
+    .collect::<Vec<_>>();
+
+  let groups = group(&users, pairs);
+
+  if args.json {
+    println!("{}", serde_json::to_string_pretty(&groups).unwrap());
+  } else {
+    print_text(&groups);
+  }
+}
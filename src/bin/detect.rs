@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Local};
+use clap::Parser;
+use futures::TryStreamExt;
+
+use yur_paintboard::{
+  db::{CanvasArgs, DbArgs},
+  detect::{longest_session, save_flag, score, Sample, FLAG_SCORE},
+  entities::paint::PaintKind,
+  query::{stream_paints, PaintFilter},
+};
+
+#[derive(Parser)]
+#[command(name = "detect")]
+#[command(author = "yurzhang")]
+#[command(about = "Score users on how bot-like their paints look.")]
+#[command(version, long_about = None)]
+struct Args {
+  /// Only paints since this time, RFC 3339
+  #[arg(long)]
+  since: Option<DateTime<Local>>,
+
+  /// Only list users scoring at least this
+  #[arg(long, default_value_t = FLAG_SCORE)]
+  min_score: f64,
+
+  /// Flag the listed users to moderators
+  #[arg(long)]
+  flag: bool,
+
+  #[command(flatten)]
+  canvas: CanvasArgs,
+
+  #[command(flatten)]
+  db: DbArgs,
+}
+
+#[tokio::main]
+async fn main() {
+  let args = Args::parse();
+
+  let db = args.db.connect().await;
+
+  let canvas = args.canvas.find(&db).await;
+
+  let filter = PaintFilter {
+    canvas_id: Some(canvas.id),
+    kind: Some(PaintKind::Paint),
+    since: args.since,
+    ..Default::default()
+  };
+
+  let mut samples = HashMap::<i32, Vec<Sample>>::new();
+
+  {
+    let paints = stream_paints(&db, &filter)
+      .await
+      .expect("Error fetching paints!");
+    futures::pin_mut!(paints);
+
+    while let Some(paint) = paints.try_next().await.expect("Error fetching paints!") {
+      let sample = Sample {
+        time: paint.time,
+        x: paint.x,
+        y: paint.y,
+      };
+
+      samples.entry(paint.uid).or_default().push(sample);
+    }
+  }
+
+  let mut verdicts = samples
+    .iter()
+    .filter_map(|(uid, samples)| {
+      let verdict = score(samples, longest_session(samples))?;
+      Some((*uid, verdict))
+    })
+    .filter(|(_, verdict)| verdict.score >= args.min_score)
+    .collect::<Vec<_>>();
+
+  verdicts.sort_by(|a, b| b.1.score.total_cmp(&a.1.score));
+
+  println!("{} of {} users scored:", verdicts.len(), samples.len());
+
+  for (uid, verdict) in &verdicts {
+    let reasons = if verdict.reasons.is_empty() {
+      "-".to_owned()
+    } else {
+      verdict.reasons.join(", ")
+    };
+
+    println!(
+      "UID: {uid:6}, Score: {:.2}, Paints: {:6}, Interval CV: {:.3}, Stride: {:.2}, Session: {}h{:02}m, {}",
+      verdict.score,
+      verdict.samples,
+      verdict.interval_cv,
+      verdict.stride_ratio,
+      verdict.session / 3600,
+      verdict.session % 3600 / 60,
+      reasons,
+    );
+  }
+
+  if args.flag {
+    for (uid, verdict) in &verdicts {
+      save_flag(&db, *uid, canvas.id, "offline", verdict)
+        .await
+        .expect("Error saving flag!");
+    }
+
+    println!("Flagged {} users.", verdicts.len());
+  }
+}
@@ -0,0 +1,231 @@
+use chrono::{DateTime, Local, NaiveTime};
+use clap::{Parser, Subcommand};
+use sea_orm::{ActiveModelTrait, ActiveValue, DatabaseConnection, EntityTrait, QueryOrder};
+
+use yur_paintboard::{
+  board::{fill_board, save_canvas},
+  consts::{BEGIN_TIME, END_TIME, HEIGHT, WIDTH},
+  db::DbArgs,
+  entities::{
+    canvas,
+    event::{self, EventStatus},
+    prelude::*,
+  },
+  pixel::{check_color, color_to_int, hex_to_bin, parse_palette},
+  query::{active_event, find_canvas, find_event},
+};
+
+#[derive(Parser)]
+#[command(name = "event")]
+#[command(author = "yurzhang")]
+#[command(about = "Manage painting events.")]
+#[command(version, long_about = None)]
+struct Args {
+  #[command(subcommand)]
+  command: Command,
+
+  #[command(flatten)]
+  db: DbArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+  /// Create an event along with a fresh canvas of the same name
+  Create {
+    name: String,
+
+    /// First moment of the event, RFC 3339
+    #[arg(long)]
+    starts_at: DateTime<Local>,
+
+    /// Last moment of the event, RFC 3339
+    #[arg(long)]
+    ends_at: DateTime<Local>,
+
+    #[arg(long, default_value_t = WIDTH)]
+    width: u16,
+
+    #[arg(long, default_value_t = HEIGHT)]
+    height: u16,
+
+    /// Painting is allowed from this time of a day
+    #[arg(long, default_value_t = *BEGIN_TIME)]
+    begin: NaiveTime,
+
+    /// Painting is allowed until this time of a day
+    #[arg(long, default_value_t = *END_TIME)]
+    end: NaiveTime,
+
+    /// Allowed colors, e.g. `#ffffff,#000000`, any color if not set
+    #[arg(long)]
+    palette: Option<String>,
+
+    #[arg(short, long, default_value_t = String::from("#ffffff"))]
+    color: String,
+  },
+  /// Make a pending event the one served on `/ws`
+  Start { name: String },
+  /// Stop accepting paints on the active event
+  Close { name: String },
+  /// Stop loading a closed event in the server, its history is kept
+  Archive { name: String },
+  /// List all events
+  List,
+}
+
+fn fail(msg: impl std::fmt::Display) -> ! {
+  eprintln!("{msg}");
+  std::process::exit(1);
+}
+
+async fn get_event(db: &DatabaseConnection, name: &str) -> event::Model {
+  let event = find_event(db, name).await.expect("Error fetching event!");
+
+  match event {
+    Some(event) => event,
+    None => fail(format!("No such event: {name}")),
+  }
+}
+
+async fn set_status(db: &DatabaseConnection, event: event::Model, status: EventStatus) {
+  let mut event: event::ActiveModel = event.into();
+
+  if status == EventStatus::Closed {
+    let now = Local::now();
+
+    // closed early
+    if *event.ends_at.as_ref() > now {
+      event.ends_at = ActiveValue::set(now);
+    }
+  }
+
+  event.status = ActiveValue::set(status);
+  event.update(db).await.expect("Error saving event!");
+
+  println!("Done, restart the server to apply.");
+}
+
+#[tokio::main]
+async fn main() {
+  let args = Args::parse();
+
+  let db = args.db.connect().await;
+
+  match args.command {
+    Command::Create {
+      name,
+      starts_at,
+      ends_at,
+      width,
+      height,
+      begin,
+      end,
+      palette,
+      color,
+    } => {
+      if starts_at >= ends_at {
+        fail("The event must start before it ends");
+      }
+
+      if !check_color(&color) {
+        fail(format!("Invalid color: {color}"));
+      }
+
+      if let Some(palette) = &palette {
+        if parse_palette(palette).is_none() {
+          fail(format!("Invalid palette: {palette}"));
+        }
+      }
+
+      let existed = find_canvas(&db, &name)
+        .await
+        .expect("Error fetching canvas!");
+
+      if existed.is_some() {
+        fail(format!("Canvas {name} already exists"));
+      }
+
+      let [r, g, b] = hex_to_bin(&color);
+
+      let new_canvas = canvas::ActiveModel {
+        name: ActiveValue::set(name.clone()),
+        width: ActiveValue::set(width.into()),
+        height: ActiveValue::set(height.into()),
+        begin_time: ActiveValue::set(begin),
+        end_time: ActiveValue::set(end),
+        palette: ActiveValue::set(palette),
+        background: ActiveValue::set(color_to_int((r, g, b))),
+        ..Default::default()
+      };
+
+      let canvas = save_canvas(&db, new_canvas)
+        .await
+        .expect("Error saving canvas!");
+
+      fill_board(&db, &canvas)
+        .await
+        .expect("Error filling board!");
+
+      let new_event = event::ActiveModel {
+        name: ActiveValue::set(name),
+        canvas_id: ActiveValue::set(canvas.id),
+        starts_at: ActiveValue::set(starts_at),
+        ends_at: ActiveValue::set(ends_at),
+        status: ActiveValue::set(EventStatus::Pending),
+        ..Default::default()
+      };
+
+      new_event.insert(&db).await.expect("Error saving event!");
+    }
+    Command::Start { name } => {
+      let event = get_event(&db, &name).await;
+
+      if event.status != EventStatus::Pending {
+        fail(format!("Event {name} is {:?}, not pending", event.status));
+      }
+
+      let active = active_event(&db).await.expect("Error fetching event!");
+
+      if let Some(active) = active {
+        fail(format!("Event {} is still active", active.name));
+      }
+
+      set_status(&db, event, EventStatus::Active).await;
+    }
+    Command::Close { name } => {
+      let event = get_event(&db, &name).await;
+
+      if event.status != EventStatus::Active {
+        fail(format!("Event {name} is {:?}, not active", event.status));
+      }
+
+      set_status(&db, event, EventStatus::Closed).await;
+    }
+    Command::Archive { name } => {
+      let event = get_event(&db, &name).await;
+
+      if event.status != EventStatus::Closed {
+        fail(format!("Event {name} is {:?}, not closed", event.status));
+      }
+
+      set_status(&db, event, EventStatus::Archived).await;
+    }
+    Command::List => {
+      let events = Event::find()
+        .order_by_asc(event::Column::StartsAt)
+        .all(&db)
+        .await
+        .expect("Error fetching events!");
+
+      for event in events {
+        println!(
+          "{}\t{:?}\t{} ~ {}",
+          event.name,
+          event.status,
+          event.starts_at.to_rfc3339(),
+          event.ends_at.to_rfc3339(),
+        );
+      }
+    }
+  }
+}
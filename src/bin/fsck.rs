@@ -0,0 +1,187 @@
+use std::collections::HashSet;
+
+use clap::{Parser, ValueEnum};
+use sea_orm::{
+  ActiveValue, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, TransactionTrait,
+};
+
+use yur_paintboard::{
+  db::{CanvasArgs, DbArgs},
+  entities::{board, canvas, paint, prelude::*},
+  replay::{replay_paints, restore_board, Cell, Frame},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Repair {
+  /// Rewrite the `board` table from the paint log
+  Board,
+  /// Add paints for pixels of the `board` table newer than the log
+  Paint,
+}
+
+#[derive(Parser)]
+#[command(name = "fsck")]
+#[command(author = "yurzhang")]
+#[command(about = "Check the board against the paint log.")]
+#[command(version, long_about = None)]
+struct Args {
+  /// Fix mismatches in the given direction, the server must be stopped
+  #[arg(long)]
+  repair: Option<Repair>,
+
+  #[command(flatten)]
+  canvas: CanvasArgs,
+
+  #[command(flatten)]
+  db: DbArgs,
+}
+
+/// Whether the pixel in the table is what the log says.
+fn same(table: &board::Model, log: &Cell) -> bool {
+  if table.color != log.color || table.uid != log.uid {
+    return false;
+  }
+
+  // never painted pixels carry the time of `setup`
+  log.uid == -1 || table.time == log.time
+}
+
+async fn repair_paint(db: &DatabaseConnection, canvas: &canvas::Model, pixels: &[board::Model]) {
+  let tasks = pixels
+    .iter()
+    .map(|pixel| paint::ActiveModel {
+      x: ActiveValue::set(pixel.x),
+      y: ActiveValue::set(pixel.y),
+      color: ActiveValue::set(pixel.color),
+      uid: ActiveValue::set(pixel.uid),
+      time: ActiveValue::set(pixel.time),
+      canvas_id: ActiveValue::set(canvas.id),
+      ..Default::default()
+    })
+    .collect::<Vec<_>>();
+
+  let res = async {
+    let txn = db.begin().await?;
+
+    // TODO(config)
+    for task in tasks.chunks(600) {
+      Paint::insert_many(task.to_owned()).exec(&txn).await?;
+    }
+
+    txn.commit().await
+  }
+  .await;
+
+  res.expect("Error inserting paints!");
+}
+
+#[tokio::main]
+async fn main() {
+  let args = Args::parse();
+
+  let db = args.db.connect().await;
+
+  let canvas = args.canvas.find(&db).await;
+
+  // the whole log, keyframes are taken from the board and may be wrong too
+  let mut log = Frame::blank(&canvas);
+
+  replay_paints(&db, &canvas, &mut log, None, None)
+    .await
+    .expect("Error replaying paints!");
+
+  let table = Board::find()
+    .filter(board::Column::CanvasId.eq(canvas.id))
+    .all(&db)
+    .await
+    .expect("Error fetching board!");
+
+  let mut seen = HashSet::new();
+  let mut outside = 0;
+  let mut mismatches = 0;
+  let mut newer = vec![];
+
+  for pixel in &table {
+    let pos = (pixel.x as u16, pixel.y as u16);
+
+    let cell = match log.get(pos.0, pos.1) {
+      Some(cell) if pixel.x >= 0 && pixel.y >= 0 => cell,
+      _ => {
+        println!("({}, {}): outside of the canvas", pixel.x, pixel.y);
+        outside += 1;
+        continue;
+      }
+    };
+
+    seen.insert(pos);
+
+    if same(pixel, cell) {
+      continue;
+    }
+
+    println!(
+      "({}, {}): board #{:06x} by {} at {}, log #{:06x} by {} at {}",
+      pixel.x,
+      pixel.y,
+      pixel.color,
+      pixel.uid,
+      pixel.time.to_rfc3339(),
+      cell.color,
+      cell.uid,
+      cell.time.to_rfc3339(),
+    );
+    mismatches += 1;
+
+    // a paint lost from the log
+    if pixel.uid != -1 && pixel.time > cell.time {
+      newer.push(pixel.clone());
+    }
+  }
+
+  let mut missing = 0;
+
+  for ((x, y), _) in log.iter() {
+    if !seen.contains(&(x, y)) {
+      println!("({x}, {y}): missing from the board");
+      missing += 1;
+    }
+  }
+
+  println!(
+    "{mismatches} mismatched, {missing} missing, {outside} outside, {} newer than the log.",
+    newer.len(),
+  );
+
+  match args.repair {
+    Some(Repair::Board) => {
+      Board::delete_many()
+        .filter(board::Column::CanvasId.eq(canvas.id))
+        .filter(
+          board::Column::X
+            .gte(canvas.width)
+            .or(board::Column::Y.gte(canvas.height))
+            .or(board::Column::X.lt(0))
+            .or(board::Column::Y.lt(0)),
+        )
+        .exec(&db)
+        .await
+        .expect("Error deleting pixels!");
+
+      restore_board(&db, &canvas, &log)
+        .await
+        .expect("Error restoring board!");
+
+      println!("Board rewritten from the log.");
+    }
+    Some(Repair::Paint) => {
+      repair_paint(&db, &canvas, &newer).await;
+
+      println!(
+        "Added {} paints, {} pixels can only be fixed with `--repair board`.",
+        newer.len(),
+        mismatches + missing - newer.len(),
+      );
+    }
+    None => {}
+  }
+}
@@ -0,0 +1,178 @@
+use chrono::{DateTime, Local};
+use clap::{Parser, Subcommand};
+use sea_orm::{ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, QueryFilter};
+
+use yur_paintboard::{
+  db::{CanvasArgs, DbArgs},
+  entities::{prelude::*, region},
+  query::Rect,
+  region::{mask_from_image, parse_list, ProtectedRegion},
+};
+
+#[derive(Parser)]
+#[command(name = "region")]
+#[command(author = "yurzhang")]
+#[command(about = "Manage protected regions of a canvas.")]
+#[command(version, long_about = None)]
+struct Args {
+  #[command(subcommand)]
+  command: Command,
+
+  #[command(flatten)]
+  canvas: CanvasArgs,
+
+  #[command(flatten)]
+  db: DbArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+  /// Protect the rectangle `x0..x1` × `y0..y1`
+  Add {
+    name: String,
+
+    #[arg(long)]
+    x0: u16,
+
+    #[arg(long)]
+    y0: u16,
+
+    #[arg(long)]
+    x1: u16,
+
+    #[arg(long)]
+    y1: u16,
+
+    /// Image of the rectangle's size, only its non-transparent pixels are protected
+    #[arg(long)]
+    mask: Option<String>,
+
+    /// Roles allowed to paint inside, e.g. `admin,sponsor`
+    #[arg(long)]
+    roles: Option<String>,
+
+    /// Users allowed to paint inside, e.g. `1,2,3`
+    #[arg(long)]
+    uids: Option<String>,
+
+    /// Protected from this time on, RFC 3339
+    #[arg(long)]
+    starts_at: Option<DateTime<Local>>,
+
+    /// Protected until this time, RFC 3339
+    #[arg(long)]
+    ends_at: Option<DateTime<Local>>,
+  },
+  /// Remove a region
+  Remove { name: String },
+  /// List all regions of the canvas
+  List,
+}
+
+fn fail(msg: impl std::fmt::Display) -> ! {
+  eprintln!("{msg}");
+  std::process::exit(1);
+}
+
+#[tokio::main]
+async fn main() {
+  let args = Args::parse();
+
+  let db = args.db.connect().await;
+
+  let canvas = args.canvas.find(&db).await;
+
+  match args.command {
+    Command::Add {
+      name,
+      x0,
+      y0,
+      x1,
+      y1,
+      mask,
+      roles,
+      uids,
+      starts_at,
+      ends_at,
+    } => {
+      let rect = Rect { x0, y0, x1, y1 };
+
+      if x0 >= x1 || y0 >= y1 || i32::from(x1) > canvas.width || i32::from(y1) > canvas.height {
+        fail("Invalid rectangle");
+      }
+
+      if let Some(uids) = &uids {
+        if parse_list::<i32>(uids).is_none() {
+          fail(format!("Invalid uids: {uids}"));
+        }
+      }
+
+      let mask = mask.map(|path| {
+        let image = image::open(&path)
+          .unwrap_or_else(|err| fail(format!("Error opening {path}: {err}")))
+          .to_rgba8();
+
+        mask_from_image(&rect, &image)
+          .unwrap_or_else(|| fail("The mask must be of the rectangle's size"))
+      });
+
+      let new_region = region::ActiveModel {
+        canvas_id: ActiveValue::set(canvas.id),
+        name: ActiveValue::set(name),
+        x0: ActiveValue::set(x0.into()),
+        y0: ActiveValue::set(y0.into()),
+        x1: ActiveValue::set(x1.into()),
+        y1: ActiveValue::set(y1.into()),
+        mask: ActiveValue::set(mask),
+        roles: ActiveValue::set(roles),
+        uids: ActiveValue::set(uids),
+        starts_at: ActiveValue::set(starts_at),
+        ends_at: ActiveValue::set(ends_at),
+        ..Default::default()
+      };
+
+      new_region.insert(&db).await.expect("Error saving region!");
+
+      println!("Done, restart the server to apply.");
+    }
+    Command::Remove { name } => {
+      let res = Region::delete_many()
+        .filter(region::Column::CanvasId.eq(canvas.id))
+        .filter(region::Column::Name.eq(name.as_str()))
+        .exec(&db)
+        .await
+        .expect("Error deleting region!");
+
+      if res.rows_affected == 0 {
+        fail(format!("No such region: {name}"));
+      }
+
+      println!("Done, restart the server to apply.");
+    }
+    Command::List => {
+      let regions = Region::find()
+        .filter(region::Column::CanvasId.eq(canvas.id))
+        .all(&db)
+        .await
+        .expect("Error fetching regions!");
+
+      for model in regions {
+        let summary = format!(
+          "{}\t({}, {}) ~ ({}, {})\tmask: {}\troles: {}\tuids: {}",
+          model.name,
+          model.x0,
+          model.y0,
+          model.x1,
+          model.y1,
+          model.mask.is_some(),
+          model.roles.as_deref().unwrap_or("-"),
+          model.uids.as_deref().unwrap_or("-"),
+        );
+
+        let valid = ProtectedRegion::from_model(model).is_some();
+
+        println!("{summary}{}", if valid { "" } else { "\t(invalid)" });
+      }
+    }
+  }
+}
@@ -0,0 +1,96 @@
+use chrono::{DateTime, Local};
+use clap::{Parser, Subcommand};
+
+use yur_paintboard::{
+  db::{CanvasArgs, DbArgs},
+  replay::{board_at, load_board, restore_board, save_keyframe},
+};
+
+#[derive(Parser)]
+#[command(name = "replay")]
+#[command(author = "yurzhang")]
+#[command(about = "Rebuild the paintboard at a point in time from the paint log.")]
+#[command(version, long_about = None)]
+struct Args {
+  /// Point in time to rebuild, RFC 3339, now if not set
+  #[arg(long)]
+  at: Option<DateTime<Local>>,
+
+  #[command(subcommand)]
+  command: Command,
+
+  #[command(flatten)]
+  canvas: CanvasArgs,
+
+  #[command(flatten)]
+  db: DbArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+  /// Save the rebuilt board to an image file
+  Image {
+    #[arg(short, long, default_value_t = String::from("replay.png"))]
+    output: String,
+  },
+  /// Print pixels where the rebuilt board differs from the `board` table
+  Diff,
+  /// Overwrite the `board` table with the rebuilt board, the server must be stopped
+  Restore,
+  /// Store the rebuilt board as a keyframe, e.g. for logs older than keyframes
+  Keyframe,
+}
+
+#[tokio::main]
+async fn main() {
+  let args = Args::parse();
+
+  let db = args.db.connect().await;
+
+  let canvas = args.canvas.find(&db).await;
+
+  let at = args.at.unwrap_or_else(Local::now);
+
+  let frame = board_at(&db, &canvas, at)
+    .await
+    .expect("Error rebuilding board!");
+
+  match args.command {
+    Command::Image { output } => {
+      frame.to_image().save(output).unwrap();
+    }
+    Command::Diff => {
+      let board = load_board(&db, &canvas)
+        .await
+        .expect("Error fetching board!");
+
+      let mut diff = 0;
+
+      for ((x, y), cell) in frame.iter() {
+        let now = board.get(x, y).unwrap();
+
+        if now.color != cell.color {
+          println!(
+            "({x}, {y}): board #{:06x} by {}, log #{:06x} by {}",
+            now.color, now.uid, cell.color, cell.uid,
+          );
+          diff += 1;
+        }
+      }
+
+      println!("{diff} pixels differ.");
+    }
+    Command::Restore => {
+      restore_board(&db, &canvas, &frame)
+        .await
+        .expect("Error restoring board!");
+
+      println!("Board restored to {}.", at.to_rfc3339());
+    }
+    Command::Keyframe => {
+      save_keyframe(&db, canvas.id, at, &frame)
+        .await
+        .expect("Error saving keyframe!");
+    }
+  }
+}
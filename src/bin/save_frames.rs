@@ -1,58 +1,69 @@
-use std::collections::HashMap;
-
 use chrono::{Local, TimeZone};
-use sea_orm::{Database, EntityTrait};
+use clap::Parser;
+use futures::TryStreamExt;
 
 use yur_paintboard::{
-  consts::{HEIGHT, WIDTH},
-  entities::prelude::*,
-  pixel::hex_to_bin,
+  db::{CanvasArgs, DbArgs},
+  query::{stream_paints, PaintFilter},
+  replay::board_at,
 };
 
+#[derive(Parser)]
+#[command(name = "save_frames")]
+#[command(author = "yurzhang")]
+#[command(about = "Save the painting history as timelapse frames.")]
+#[command(version, long_about = None)]
+struct Args {
+  #[command(flatten)]
+  canvas: CanvasArgs,
+
+  #[command(flatten)]
+  db: DbArgs,
+}
+
 #[tokio::main]
 async fn main() {
-  let db = Database::connect("sqlite:./data.db?mode=rwc")
-    .await
-    .expect("Error opening database!");
+  let args = Args::parse();
 
-  let actions = Paint::find()
-    .all(&db)
-    .await
-    .expect("Error fetching actions!");
-
-  let mut board = HashMap::new();
+  let db = args.db.connect().await;
 
-  for x in 0..WIDTH {
-    for y in 0..HEIGHT {
-      board.insert((x, y), [240, 240, 240]);
-    }
-  }
+  let canvas = args.canvas.find(&db).await;
 
   let mut begin_time = Local.with_ymd_and_hms(2023, 1, 29, 20, 0, 0).unwrap();
   let end_time = Local.with_ymd_and_hms(2023, 1, 29, 22, 0, 0).unwrap();
 
-  let mut action_idx = 0;
+  // start from the nearest keyframe instead of replaying everything
+  let mut frame = board_at(&db, &canvas, begin_time)
+    .await
+    .expect("Error rebuilding board!");
+
+  let filter = PaintFilter {
+    canvas_id: Some(canvas.id),
+    since: Some(begin_time),
+    until: Some(end_time),
+    ..Default::default()
+  };
+
+  let actions = stream_paints(&db, &filter)
+    .await
+    .expect("Error fetching actions!");
+  futures::pin_mut!(actions);
+
+  let mut next_action = actions.try_next().await.expect("Error fetching actions!");
   let mut pic_idx = 1;
 
   std::fs::create_dir_all("./frames").unwrap();
 
   while begin_time <= end_time {
-    while action_idx < actions.len() && actions[action_idx].time < begin_time {
-      let action = &actions[action_idx];
-      let pos = (action.x as u16, action.y as u16);
-      board.insert(pos, hex_to_bin(&action.color));
-      action_idx += 1;
-    }
-
-    let mut imgbuf = image::ImageBuffer::new(WIDTH.into(), HEIGHT.into());
-
-    for item in board.iter() {
-      let (x, y) = *item.0;
-      let pixel = imgbuf.get_pixel_mut(x.into(), y.into());
-      *pixel = image::Rgb(*item.1);
+    while let Some(action) = next_action.as_ref().filter(|a| a.time < begin_time) {
+      frame.apply(action);
+      next_action = actions.try_next().await.expect("Error fetching actions!");
     }
 
-    imgbuf.save(format!("./frames/{pic_idx}.png")).unwrap();
+    frame
+      .to_image()
+      .save(format!("./frames/{pic_idx}.png"))
+      .unwrap();
 
     begin_time += chrono::Duration::seconds(1);
     pic_idx += 1;
@@ -1,10 +1,10 @@
 use clap::Parser;
-use sea_orm::{Database, EntityTrait};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
 
 use yur_paintboard::{
-  consts::{HEIGHT, WIDTH},
-  entities::prelude::*,
-  pixel::hex_to_bin,
+  db::{CanvasArgs, DbArgs},
+  entities::{board, prelude::*},
+  pixel::int_to_bin,
 };
 
 #[derive(Parser)]
@@ -15,27 +15,37 @@ use yur_paintboard::{
 struct Args {
   #[arg(short, long, default_value_t = String::from("result.png"))]
   output: String,
+
+  #[command(flatten)]
+  canvas: CanvasArgs,
+
+  #[command(flatten)]
+  db: DbArgs,
 }
 
 #[tokio::main]
 async fn main() {
   let args = Args::parse();
 
-  let db = Database::connect("sqlite:./data.db?mode=rwc")
-    .await
-    .expect("Error opening database!");
+  let db = args.db.connect().await;
 
-  let board = Board::find().all(&db).await.expect("Error fetching board!");
+  let canvas = args.canvas.find(&db).await;
+
+  let board = Board::find()
+    .filter(board::Column::CanvasId.eq(canvas.id))
+    .all(&db)
+    .await
+    .expect("Error fetching board!");
 
-  let width = WIDTH.into();
-  let height = HEIGHT.into();
+  let width = canvas.width as u32;
+  let height = canvas.height as u32;
 
   let mut imgbuf = image::ImageBuffer::new(width, height);
 
   for item in board {
     let x = item.x as u32;
     let y = item.y as u32;
-    let color = hex_to_bin(&item.color);
+    let color = int_to_bin(item.color);
 
     let pixel = imgbuf.get_pixel_mut(x, y);
     *pixel = image::Rgb(color);
@@ -1,10 +1,13 @@
-use chrono::Local;
+use chrono::NaiveTime;
 use clap::Parser;
-use sea_orm::{sea_query::OnConflict, ActiveValue, Database, EntityTrait};
+use sea_orm::ActiveValue;
 
 use yur_paintboard::{
-  consts::{HEIGHT, WIDTH},
-  entities::{board, prelude::*},
+  board::{fill_board, save_canvas},
+  consts::{BEGIN_TIME, DEFAULT_CANVAS, END_TIME, HEIGHT, WIDTH},
+  db::DbArgs,
+  entities::canvas,
+  pixel::{check_color, color_to_int, hex_to_bin, parse_palette},
 };
 
 #[derive(Parser)]
@@ -15,24 +18,31 @@ use yur_paintboard::{
 struct Args {
   #[clap(short, long, default_value_t = String::from("#ffffff"))]
   color: String,
-}
 
-fn check_color(color: &str) -> bool {
-  if color.len() != 7 {
-    return false;
-  }
+  /// Name of the canvas, created if it does not exist
+  #[arg(long, default_value_t = String::from(DEFAULT_CANVAS))]
+  canvas: String,
 
-  if !color.starts_with('#') {
-    return false;
-  }
+  #[arg(long, default_value_t = WIDTH)]
+  width: u16,
 
-  for c in color.chars().skip(1) {
-    if !c.is_ascii_hexdigit() {
-      return false;
-    }
-  }
+  #[arg(long, default_value_t = HEIGHT)]
+  height: u16,
+
+  /// Painting is allowed from this time of a day
+  #[arg(long, default_value_t = *BEGIN_TIME)]
+  begin: NaiveTime,
+
+  /// Painting is allowed until this time of a day
+  #[arg(long, default_value_t = *END_TIME)]
+  end: NaiveTime,
+
+  /// Allowed colors, e.g. `#ffffff,#000000`, any color if not set
+  #[arg(long)]
+  palette: Option<String>,
 
-  true
+  #[command(flatten)]
+  db: DbArgs,
 }
 
 #[tokio::main]
@@ -44,36 +54,34 @@ async fn main() {
     std::process::exit(1);
   }
 
-  let db = Database::connect("sqlite:./data.db?mode=rwc")
-    .await
-    .expect("Error opening database!");
-
-  let now = Local::now();
-
-  for x in 0..WIDTH {
-    let tasks = (0..HEIGHT).map(|y| board::ActiveModel {
-      x: ActiveValue::set(x.into()),
-      y: ActiveValue::set(y.into()),
-      color: ActiveValue::set(args.color.clone()),
-      uid: ActiveValue::set(-1),
-      time: ActiveValue::set(now),
-    });
-
-    let res = Board::insert_many(tasks)
-      .on_conflict(
-        OnConflict::columns([board::Column::X, board::Column::Y])
-          .update_columns([
-            board::Column::Color,
-            board::Column::Uid,
-            board::Column::Time,
-          ])
-          .to_owned(),
-      )
-      .exec(&db)
-      .await;
-
-    if let Err(err) = res {
-      eprintln!("[C{x}] Error inserting pixel: {err}");
+  if let Some(palette) = &args.palette {
+    if parse_palette(palette).is_none() {
+      eprintln!("Invalid palette: {palette}");
+      std::process::exit(1);
     }
   }
+
+  let [r, g, b] = hex_to_bin(&args.color);
+  let color = color_to_int((r, g, b));
+
+  let db = args.db.connect().await;
+
+  let new_canvas = canvas::ActiveModel {
+    name: ActiveValue::set(args.canvas.clone()),
+    width: ActiveValue::set(args.width.into()),
+    height: ActiveValue::set(args.height.into()),
+    begin_time: ActiveValue::set(args.begin),
+    end_time: ActiveValue::set(args.end),
+    palette: ActiveValue::set(args.palette.clone()),
+    background: ActiveValue::set(color),
+    ..Default::default()
+  };
+
+  let canvas = save_canvas(&db, new_canvas)
+    .await
+    .expect("Error saving canvas!");
+
+  fill_board(&db, &canvas)
+    .await
+    .expect("Error filling board!");
 }
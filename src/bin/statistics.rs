@@ -1,17 +1,28 @@
 use std::collections::HashMap;
 
 use chrono::Local;
-use sea_orm::{Database, DatabaseConnection, EntityTrait};
-
-use yur_paintboard::entities::{board, prelude::*};
-
-async fn board(db: &DatabaseConnection) {
-  let board = Board::find().all(db).await.expect("Error fetching board!");
+use clap::Parser;
+use futures::TryStreamExt;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+
+use yur_paintboard::{
+  db::{CanvasArgs, DbArgs},
+  entities::{board, canvas, paint::PaintKind, prelude::*},
+  query::{stream_paints, PaintFilter},
+};
+
+async fn board(db: &DatabaseConnection, canvas: &canvas::Model) {
+  let board = Board::find()
+    .filter(board::Column::CanvasId.eq(canvas.id))
+    .all(db)
+    .await
+    .expect("Error fetching board!");
 
   let mut earliest_paint = board::Model {
+    canvas_id: canvas.id,
     x: -1,
     y: -1,
-    color: "#ffffff".to_owned(),
+    color: 0xffffff,
     uid: -1,
     time: Local::now(),
   };
@@ -50,13 +61,22 @@ async fn board(db: &DatabaseConnection) {
   println!();
 }
 
-async fn actions(db: &DatabaseConnection) {
-  let actions = Paint::find().all(db).await.expect("Error fetching paint!");
+async fn actions(db: &DatabaseConnection, canvas: &canvas::Model) {
+  let filter = PaintFilter {
+    canvas_id: Some(canvas.id),
+    kind: Some(PaintKind::Paint),
+    ..Default::default()
+  };
+
+  let actions = stream_paints(db, &filter)
+    .await
+    .expect("Error fetching paint!");
+  futures::pin_mut!(actions);
 
   let mut paint_info = HashMap::new();
   let mut pixel_num = 0;
 
-  for action in actions.iter() {
+  while let Some(action) = actions.try_next().await.expect("Error fetching paint!") {
     let uid = action.uid;
 
     paint_info
@@ -76,12 +96,27 @@ async fn actions(db: &DatabaseConnection) {
   }
 }
 
+#[derive(Parser)]
+#[command(name = "statistics")]
+#[command(author = "yurzhang")]
+#[command(about = "Print statistics of the paintboard.")]
+#[command(version, long_about = None)]
+struct Args {
+  #[command(flatten)]
+  canvas: CanvasArgs,
+
+  #[command(flatten)]
+  db: DbArgs,
+}
+
 #[tokio::main]
 async fn main() {
-  let db = Database::connect("sqlite:./data.db?mode=rwc")
-    .await
-    .expect("Error opening database!");
+  let args = Args::parse();
+
+  let db = args.db.connect().await;
+
+  let canvas = args.canvas.find(&db).await;
 
-  board(&db).await;
-  actions(&db).await;
+  board(&db, &canvas).await;
+  actions(&db, &canvas).await;
 }
@@ -0,0 +1,131 @@
+use chrono::{DateTime, Local};
+use sea_orm::{
+  sea_query::OnConflict, ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseConnection, DbErr,
+  EntityTrait, QueryFilter, TransactionTrait,
+};
+
+use crate::{
+  board_file::remove_board_file,
+  entities::{board, canvas, prelude::*},
+  query::find_canvas,
+};
+
+/// Create the canvas named in `new_canvas`, or update the existing one
+/// with the same name.
+pub async fn save_canvas(
+  db: &DatabaseConnection,
+  mut new_canvas: canvas::ActiveModel,
+) -> Result<canvas::Model, DbErr> {
+  let name = new_canvas.name.clone().unwrap();
+
+  match find_canvas(db, &name).await? {
+    Some(old_canvas) => {
+      new_canvas.id = ActiveValue::unchanged(old_canvas.id);
+      new_canvas.update(db).await
+    }
+    None => new_canvas.insert(db).await,
+  }
+}
+
+/// Paint the whole canvas with its background, dropping pixels outside of it.
+pub async fn fill_board(db: &DatabaseConnection, canvas: &canvas::Model) -> Result<(), DbErr> {
+  remove_board_file(&canvas.name)
+    .map_err(|err| DbErr::Custom(format!("Error removing board file: {err}")))?;
+
+  // drop pixels left outside by a smaller size
+  Board::delete_many()
+    .filter(board::Column::CanvasId.eq(canvas.id))
+    .filter(
+      board::Column::X
+        .gte(canvas.width)
+        .or(board::Column::Y.gte(canvas.height)),
+    )
+    .exec(db)
+    .await?;
+
+  let now = Local::now();
+
+  for x in 0..canvas.width {
+    let tasks = (0..canvas.height).map(|y| board::ActiveModel {
+      canvas_id: ActiveValue::set(canvas.id),
+      x: ActiveValue::set(x),
+      y: ActiveValue::set(y),
+      color: ActiveValue::set(canvas.background),
+      uid: ActiveValue::set(-1),
+      time: ActiveValue::set(now),
+    });
+
+    Board::insert_many(tasks)
+      .on_conflict(
+        OnConflict::columns([board::Column::CanvasId, board::Column::X, board::Column::Y])
+          .update_columns([
+            board::Column::Color,
+            board::Column::Uid,
+            board::Column::Time,
+          ])
+          .to_owned(),
+      )
+      .exec(db)
+      .await?;
+  }
+
+  Ok(())
+}
+
+/// Grow `canvas` to `width` × `height` in one transaction, filling the
+/// new area with the background at `time`. Pixels inside the old size
+/// are kept.
+pub async fn grow_board(
+  db: &DatabaseConnection,
+  canvas: &canvas::Model,
+  width: i32,
+  height: i32,
+  time: DateTime<Local>,
+) -> Result<canvas::Model, DbErr> {
+  let txn = db.begin().await?;
+
+  for x in 0..width {
+    // only the part of the column outside the old size
+    let from = if x < canvas.width { canvas.height } else { 0 };
+
+    if from >= height {
+      continue;
+    }
+
+    let tasks = (from..height).map(|y| board::ActiveModel {
+      canvas_id: ActiveValue::set(canvas.id),
+      x: ActiveValue::set(x),
+      y: ActiveValue::set(y),
+      color: ActiveValue::set(canvas.background),
+      uid: ActiveValue::set(-1),
+      time: ActiveValue::set(time),
+    });
+
+    // pixels left outside by a smaller size are stale
+    Board::insert_many(tasks)
+      .on_conflict(
+        OnConflict::columns([board::Column::CanvasId, board::Column::X, board::Column::Y])
+          .update_columns([
+            board::Column::Color,
+            board::Column::Uid,
+            board::Column::Time,
+          ])
+          .to_owned(),
+      )
+      .exec(&txn)
+      .await?;
+  }
+
+  let new_canvas = canvas::ActiveModel {
+    id: ActiveValue::unchanged(canvas.id),
+    width: ActiveValue::set(width),
+    height: ActiveValue::set(height),
+    ..Default::default()
+  };
+
+  let new_canvas = new_canvas.update(&txn).await?;
+
+  txn.commit().await?;
+
+  Ok(new_canvas)
+}
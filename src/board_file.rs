@@ -0,0 +1,101 @@
+use std::{
+  fs::{self, File},
+  io::{self, Write},
+  path::PathBuf,
+};
+
+use sea_orm::{ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder};
+
+use crate::{
+  consts::BOARD_FILE_DIR,
+  entities::{paint, prelude::*},
+  replay::Frame,
+};
+
+const MAGIC: &[u8; 4] = b"YURB";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 17;
+
+/// A copy of the board of one canvas kept next to the database, so that
+/// the server can start without reading the `board` table.
+pub struct BoardFile {
+  pub canvas_id: i32,
+  /// Every paint of the canvas up to this id is applied, `-1` for none.
+  pub last_paint_id: i32,
+  pub frame: Frame,
+}
+
+fn path(canvas: &str) -> PathBuf {
+  PathBuf::from(BOARD_FILE_DIR).join(format!("{canvas}.bin"))
+}
+
+impl BoardFile {
+  fn to_bytes(&self) -> Vec<u8> {
+    let mut res = Vec::with_capacity(HEADER_LEN);
+
+    res.extend_from_slice(MAGIC);
+    res.push(VERSION);
+    res.extend_from_slice(&self.canvas_id.to_le_bytes());
+    res.extend_from_slice(&self.frame.width.to_le_bytes());
+    res.extend_from_slice(&self.frame.height.to_le_bytes());
+    res.extend_from_slice(&self.last_paint_id.to_le_bytes());
+    res.extend_from_slice(&self.frame.to_bytes());
+
+    res
+  }
+
+  fn from_bytes(data: &[u8]) -> Option<Self> {
+    if data.len() < HEADER_LEN || &data[0..4] != MAGIC || data[4] != VERSION {
+      return None;
+    }
+
+    let width = u16::from_le_bytes([data[9], data[10]]);
+    let height = u16::from_le_bytes([data[11], data[12]]);
+
+    Some(BoardFile {
+      canvas_id: i32::from_le_bytes(data[5..9].try_into().unwrap()),
+      last_paint_id: i32::from_le_bytes(data[13..17].try_into().unwrap()),
+      frame: Frame::from_bytes(width, height, &data[HEADER_LEN..])?,
+    })
+  }
+
+  /// Read the file of `canvas`, `None` if there is none or it is corrupted.
+  pub fn read(canvas: &str) -> Option<Self> {
+    let data = fs::read(path(canvas)).ok()?;
+    BoardFile::from_bytes(&data)
+  }
+
+  /// Replace the file of `canvas`, never leaving a partial one behind.
+  pub fn write(&self, canvas: &str) -> io::Result<()> {
+    fs::create_dir_all(BOARD_FILE_DIR)?;
+
+    let path = path(canvas);
+    let tmp = path.with_extension("tmp");
+
+    let mut file = File::create(&tmp)?;
+    file.write_all(&self.to_bytes())?;
+    file.sync_all()?;
+
+    fs::rename(tmp, path)
+  }
+}
+
+/// Drop the file of `canvas` after the `board` table was rewritten
+/// by hand, so the server reads the table again.
+pub fn remove_board_file(canvas: &str) -> io::Result<()> {
+  match fs::remove_file(path(canvas)) {
+    Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+    _ => Ok(()),
+  }
+}
+
+/// The id of the latest paint of a canvas in the database, `-1` for none.
+pub async fn last_paint_id(db: &DatabaseConnection, canvas_id: i32) -> Result<i32, DbErr> {
+  let last = Paint::find()
+    .filter(paint::Column::CanvasId.eq(canvas_id))
+    .order_by_desc(paint::Column::Id)
+    .one(db)
+    .await?;
+
+  Ok(last.map_or(-1, |paint| paint.id))
+}
@@ -0,0 +1,180 @@
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Local};
+use clap::ValueEnum;
+use sea_orm::{sea_query::OnConflict, ActiveValue, DbErr, EntityTrait};
+
+use crate::{audit::audit, canvas::CanvasState, session::Notice, AppState};
+use yur_paintboard::{
+  detect::{save_flag, score, Sample, Verdict, FLAG_SCORE, MIN_SAMPLES},
+  entities::{prelude::*, shadowban},
+};
+
+// TODO(config)
+/// Paints of a session scored at once.
+const WINDOW: usize = 200;
+
+/// What happens to a user flagged by the online scoring.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BotAction {
+  /// Only flag to moderators
+  None,
+  /// Shadowban the user
+  Shadowban,
+  /// Refuse paints until the user solves a captcha
+  Captcha,
+}
+
+/// Online scoring of the paints of one session.
+pub struct Watch {
+  samples: VecDeque<Sample>,
+  since: DateTime<Local>,
+  count: usize,
+  flagged: bool,
+}
+
+impl Watch {
+  pub fn new() -> Self {
+    Watch {
+      samples: VecDeque::with_capacity(WINDOW),
+      since: Local::now(),
+      count: 0,
+      flagged: false,
+    }
+  }
+
+  /// Record a paint, every [`MIN_SAMPLES`] paints score the latest ones
+  /// and return the verdict the first time it is bad enough.
+  pub fn observe(&mut self, sample: Sample) -> Option<Verdict> {
+    if self.samples.len() == WINDOW {
+      self.samples.pop_front();
+    }
+    self.samples.push_back(sample);
+    self.count += 1;
+
+    if self.flagged || !self.count.is_multiple_of(MIN_SAMPLES) {
+      return None;
+    }
+
+    let samples = self.samples.make_contiguous();
+    let verdict = score(samples, sample.time - self.since)?;
+
+    if verdict.score < FLAG_SCORE {
+      return None;
+    }
+
+    self.flagged = true;
+
+    Some(verdict)
+  }
+}
+
+async fn auto_shadowban(state: &AppState, uid: i32) -> Result<(), DbErr> {
+  let shadowban = shadowban::ActiveModel {
+    uid: ActiveValue::set(uid),
+    reason: ActiveValue::set(Some("Bot detection".to_owned())),
+    actor: ActiveValue::set(-1),
+    time: ActiveValue::set(Local::now()),
+  };
+
+  Shadowban::insert(shadowban)
+    .on_conflict(
+      OnConflict::column(shadowban::Column::Uid)
+        .do_nothing()
+        .to_owned(),
+    )
+    .exec(&state.db)
+    .await?;
+
+  state.shadowbans.lock().insert(uid);
+
+  Ok(())
+}
+
+/// Flag a session scored as a bot to moderators, then act on it as
+/// configured. The actor of automatic actions is `-1`.
+#[tracing::instrument(skip_all, fields(uid))]
+pub async fn flag_bot(state: &AppState, canvas: &CanvasState, uid: i32, verdict: Verdict) {
+  tracing::warn!(
+    score = verdict.score,
+    reasons = ?verdict.reasons,
+    "Flagged as a bot"
+  );
+
+  if let Err(err) = save_flag(&state.db, uid, canvas.id, "online", &verdict).await {
+    tracing::error!("Save bot flag failed: {err}");
+  }
+
+  let action = match state.bot_action {
+    BotAction::None => return,
+    BotAction::Shadowban => {
+      if let Err(err) = auto_shadowban(state, uid).await {
+        tracing::error!("Auto shadowban failed: {err}");
+        return;
+      }
+
+      "shadowban"
+    }
+    BotAction::Captcha => {
+      state.challenged.lock().insert(uid, Local::now());
+
+      // fails only if nobody is listening
+      let _ = state.notices.send(Notice::Challenge(uid));
+
+      "challenge"
+    }
+  };
+
+  let params = serde_json::json!({
+    "uid": uid,
+    "reason": "Bot detection",
+    "score": verdict.score,
+  });
+
+  if let Err(err) = audit(&state.db, -1, action, params).await {
+    tracing::error!("Write audit log failed: {err}");
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::Duration;
+
+  use super::*;
+
+  fn paint(watch: &Watch, idx: usize) -> Sample {
+    Sample {
+      time: watch.since + Duration::seconds(idx as i64),
+      x: idx as i32,
+      y: 0,
+    }
+  }
+
+  #[test]
+  fn scores_every_min_samples_and_flags_once() {
+    let mut watch = Watch::new();
+
+    for idx in 1..MIN_SAMPLES {
+      assert!(watch.observe(paint(&watch, idx)).is_none());
+    }
+
+    let verdict = watch.observe(paint(&watch, MIN_SAMPLES)).unwrap();
+    assert!(verdict.score >= FLAG_SCORE);
+
+    for idx in MIN_SAMPLES + 1..=3 * MIN_SAMPLES {
+      assert!(watch.observe(paint(&watch, idx)).is_none());
+    }
+  }
+
+  #[test]
+  fn keeps_only_the_latest_window() {
+    let mut watch = Watch::new();
+
+    for idx in 0..WINDOW + 10 {
+      watch.observe(paint(&watch, idx));
+    }
+
+    assert_eq!(watch.samples.len(), WINDOW);
+    assert_eq!(watch.samples[0].x, 10);
+  }
+}
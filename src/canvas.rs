@@ -0,0 +1,396 @@
+use std::{
+  collections::{HashMap, HashSet},
+  fmt::Display,
+  sync::atomic::{AtomicBool, AtomicI32, AtomicU16, Ordering},
+};
+
+use chrono::{DateTime, Local, NaiveTime, TimeZone};
+use parking_lot::{Mutex, RwLock};
+use sea_orm::{ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter};
+use tokio::sync::broadcast::{self, Sender};
+
+use yur_paintboard::{
+  board_file::{last_paint_id, BoardFile},
+  entities::{
+    board, canvas,
+    event::{self, EventStatus},
+    prelude::*,
+    region,
+  },
+  pixel::{parse_palette, Pixel},
+  region::ProtectedRegion,
+  replay::{Cell, Frame},
+};
+
+// TODO(config)
+const MAX_REPORTED: usize = 10;
+
+pub enum LoadError {
+  Db(DbErr),
+  /// The `board` table does not cover the canvas exactly.
+  Incomplete {
+    canvas: String,
+    width: u16,
+    height: u16,
+    missing: Vec<(u16, u16)>,
+    outside: Vec<(i32, i32)>,
+  },
+}
+
+fn format_positions<T: Display>(positions: &[(T, T)]) -> String {
+  let mut res = positions
+    .iter()
+    .take(MAX_REPORTED)
+    .map(|(x, y)| format!("({x}, {y})"))
+    .collect::<Vec<_>>()
+    .join(", ");
+
+  if positions.len() > MAX_REPORTED {
+    res.push_str(", ...");
+  }
+
+  res
+}
+
+impl Display for LoadError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      LoadError::Db(err) => write!(f, "Error loading canvas: {err}"),
+      LoadError::Incomplete {
+        canvas,
+        width,
+        height,
+        missing,
+        outside,
+      } => {
+        writeln!(
+          f,
+          "Board of canvas {canvas} ({width}x{height}) is incomplete:"
+        )?;
+
+        if !missing.is_empty() {
+          writeln!(
+            f,
+            "  {} pixels missing: {}",
+            missing.len(),
+            format_positions(missing),
+          )?;
+        }
+
+        if !outside.is_empty() {
+          writeln!(
+            f,
+            "  {} pixels outside of the canvas: {}",
+            outside.len(),
+            format_positions(outside),
+          )?;
+        }
+
+        write!(
+          f,
+          "Run `fsck --repair board`, or start with `--fill-missing` to fill the gaps with the background."
+        )
+      }
+    }
+  }
+}
+
+impl From<DbErr> for LoadError {
+  fn from(err: DbErr) -> Self {
+    LoadError::Db(err)
+  }
+}
+
+/// The latest paint of a user, kept for a while so that it can be undone.
+pub struct LastPaint {
+  pub x: u16,
+  pub y: u16,
+  pub time: DateTime<Local>,
+  /// The pixel it replaced, as the paint log has it.
+  pub previous: board::Model,
+  /// The user's cooldown before it, to refund.
+  pub previous_paint: Option<DateTime<Local>>,
+  /// Painted while shadowbanned, so never applied to the board.
+  pub shadow: bool,
+}
+
+/// A board served by this server, with everything painting on it needs.
+pub struct CanvasState {
+  pub id: i32,
+  pub name: String,
+  /// Only ever grows, after the pixels of the new area are on `board`.
+  width: AtomicU16,
+  height: AtomicU16,
+  pub begin_time: NaiveTime,
+  pub end_time: NaiveTime,
+  /// Allowed colors, `None` for any color.
+  pub palette: Option<HashSet<i32>>,
+  pub background: i32,
+  pub regions: Vec<ProtectedRegion>,
+  /// Painting is allowed only in `starts_at..=ends_at` for event canvases.
+  pub schedule: Option<(DateTime<Local>, DateTime<Local>)>,
+  /// Paints are refused, e.g. on a closed event.
+  pub readonly: bool,
+  pub sender: Sender<Pixel>,
+  /// Paints of shadowbanned users with their uid, only echoed to
+  /// their own sessions.
+  pub shadow_sender: Sender<(i32, Pixel)>,
+  pub board: RwLock<HashMap<(u16, u16), Mutex<board::Model>>>,
+  pub dirty: Mutex<HashSet<(u16, u16)>>,
+  /// Whether anything was painted since the last keyframe.
+  pub painted: AtomicBool,
+  /// `last_paint_id` of the board file on disk, `i32::MIN` if none.
+  pub file_paint_id: AtomicI32,
+  pub user_paint: Mutex<HashMap<i32, DateTime<Local>>>,
+  pub last_paint: Mutex<HashMap<i32, LastPaint>>,
+}
+
+impl CanvasState {
+  pub async fn load(
+    db: &DatabaseConnection,
+    model: canvas::Model,
+    event: Option<&event::Model>,
+    fill_missing: bool,
+  ) -> Result<Self, LoadError> {
+    let width = model.width as u16;
+    let height = model.height as u16;
+
+    let mut board = HashMap::new();
+    let mut outside = vec![];
+
+    let file = BoardFile::read(&model.name).filter(|file| {
+      file.canvas_id == model.id && file.frame.width == width && file.frame.height == height
+    });
+
+    // the file is only used if no paint was saved after it
+    let file = match file {
+      Some(file) if file.last_paint_id >= last_paint_id(db, model.id).await? => Some(file),
+      _ => None,
+    };
+
+    let file_paint_id = file.as_ref().map_or(i32::MIN, |file| file.last_paint_id);
+
+    let pixels = match file {
+      Some(file) => {
+        tracing::info!(canvas = model.name, "Loading board from file...");
+
+        file
+          .frame
+          .iter()
+          .map(|((x, y), cell)| board::Model {
+            canvas_id: model.id,
+            x: x.into(),
+            y: y.into(),
+            color: cell.color,
+            uid: cell.uid,
+            time: cell.time,
+          })
+          .collect()
+      }
+      None => {
+        Board::find()
+          .filter(board::Column::CanvasId.eq(model.id))
+          .all(db)
+          .await?
+      }
+    };
+
+    for pixel in pixels {
+      if !(0..model.width).contains(&pixel.x) || !(0..model.height).contains(&pixel.y) {
+        outside.push((pixel.x, pixel.y));
+        continue;
+      }
+
+      let pos = (pixel.x as u16, pixel.y as u16);
+      board.insert(pos, Mutex::new(pixel));
+    }
+
+    let missing = (0..width)
+      .flat_map(|x| (0..height).map(move |y| (x, y)))
+      .filter(|pos| !board.contains_key(pos))
+      .collect::<Vec<_>>();
+
+    if !missing.is_empty() || !outside.is_empty() {
+      if !fill_missing {
+        return Err(LoadError::Incomplete {
+          canvas: model.name,
+          width,
+          height,
+          missing,
+          outside,
+        });
+      }
+
+      tracing::warn!(
+        canvas = model.name,
+        missing = missing.len(),
+        outside = outside.len(),
+        "Filling missing pixels, ignoring those outside."
+      );
+    }
+
+    for &(x, y) in &missing {
+      let pixel = board::Model {
+        canvas_id: model.id,
+        x: x.into(),
+        y: y.into(),
+        color: model.background,
+        uid: -1,
+        time: Local.timestamp_opt(0, 0).unwrap(),
+      };
+
+      board.insert((x, y), Mutex::new(pixel));
+    }
+
+    let palette = match &model.palette {
+      Some(palette) => {
+        let palette = parse_palette(palette).ok_or_else(|| {
+          LoadError::Db(DbErr::Custom(format!(
+            "Invalid palette of canvas {}",
+            model.name
+          )))
+        })?;
+
+        Some(palette.into_iter().collect())
+      }
+      None => None,
+    };
+
+    let regions = Region::find()
+      .filter(region::Column::CanvasId.eq(model.id))
+      .all(db)
+      .await?
+      .into_iter()
+      .map(|region| {
+        let name = region.name.clone();
+
+        ProtectedRegion::from_model(region).ok_or_else(|| {
+          LoadError::Db(DbErr::Custom(format!(
+            "Invalid region {name} of canvas {}",
+            model.name
+          )))
+        })
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+
+    // TODO(config)
+    let (sender, _) = broadcast::channel::<Pixel>(65536);
+    // TODO(config)
+    let (shadow_sender, _) = broadcast::channel(1024);
+
+    Ok(CanvasState {
+      id: model.id,
+      name: model.name,
+      width: AtomicU16::new(width),
+      height: AtomicU16::new(height),
+      begin_time: model.begin_time,
+      end_time: model.end_time,
+      palette,
+      background: model.background,
+      regions,
+      schedule: event.map(|event| (event.starts_at, event.ends_at)),
+      readonly: event.is_some_and(|event| event.status != EventStatus::Active),
+      sender,
+      shadow_sender,
+      board: RwLock::new(board),
+      // write the filled pixels back
+      dirty: Mutex::new(missing.into_iter().collect()),
+      painted: AtomicBool::new(false),
+      file_paint_id: AtomicI32::new(file_paint_id),
+      user_paint: Mutex::new(HashMap::new()),
+      last_paint: Mutex::new(HashMap::new()),
+    })
+  }
+
+  pub fn width(&self) -> u16 {
+    self.width.load(Ordering::Acquire)
+  }
+
+  pub fn height(&self) -> u16 {
+    self.height.load(Ordering::Acquire)
+  }
+
+  /// Grow the board to `width` × `height`, filling the new area with the
+  /// background. The pixels must already be in the `board` table.
+  pub fn grow(&self, width: u16, height: u16, time: DateTime<Local>) {
+    let mut board = self.board.write();
+
+    for x in 0..width {
+      for y in 0..height {
+        board.entry((x, y)).or_insert_with(|| {
+          Mutex::new(board::Model {
+            canvas_id: self.id,
+            x: x.into(),
+            y: y.into(),
+            color: self.background,
+            uid: -1,
+            time,
+          })
+        });
+      }
+    }
+
+    self.width.store(width, Ordering::Release);
+    self.height.store(height, Ordering::Release);
+
+    // the board file and keyframes must be taken again at the new size
+    self.file_paint_id.store(i32::MIN, Ordering::Relaxed);
+    self.painted.store(true, Ordering::Relaxed);
+  }
+
+  /// Copy the whole board out, for a keyframe.
+  pub fn frame(&self) -> Frame {
+    let board = self.board.read();
+    let mut frame = Frame::new(self.width(), self.height(), self.background);
+
+    for (&(x, y), pixel) in board.iter() {
+      let pixel = pixel.lock();
+
+      let cell = Cell {
+        color: pixel.color,
+        uid: pixel.uid,
+        time: pixel.time,
+      };
+
+      frame.set(x, y, cell);
+    }
+
+    frame
+  }
+
+  /// The region protecting `(x, y)` from a user, if any.
+  pub fn protected_by(
+    &self,
+    x: u16,
+    y: u16,
+    uid: i32,
+    role: Option<&str>,
+    now: DateTime<Local>,
+  ) -> Option<&ProtectedRegion> {
+    self
+      .regions
+      .iter()
+      .find(|region| region.covers(x, y, now) && !region.allows(uid, role))
+  }
+
+  /// Whether the canvas is readonly or its event is over, so that it
+  /// will not be painted any more.
+  pub fn is_closed(&self, now: DateTime<Local>) -> bool {
+    self.readonly || self.schedule.is_some_and(|(_, ends_at)| now > ends_at)
+  }
+
+  /// Whether painting is allowed at `now`.
+  pub fn is_open(&self, now: DateTime<Local>) -> bool {
+    if self.readonly {
+      return false;
+    }
+
+    if let Some((starts_at, ends_at)) = self.schedule {
+      if now < starts_at || now > ends_at {
+        return false;
+      }
+    }
+
+    now.time() >= self.begin_time && now.time() <= self.end_time
+  }
+}
@@ -1,6 +1,14 @@
 use chrono::NaiveTime;
 use lazy_static::lazy_static;
 
+/// The canvas served on `/ws` when no event is active.
+pub const DEFAULT_CANVAS: &str = "main";
+
+// TODO(config)
+/// Where the server keeps a binary copy of each board, see `board_file`.
+pub const BOARD_FILE_DIR: &str = "./boards";
+
+// defaults for new canvases
 // TODO(config)
 pub const WIDTH: u16 = 1000;
 pub const HEIGHT: u16 = 600;
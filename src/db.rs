@@ -0,0 +1,143 @@
+use std::fmt::Display;
+
+use migration::{Migrator, MigratorTrait};
+use sea_orm::{Database, DatabaseConnection, DbErr};
+
+use crate::{consts::DEFAULT_CANVAS, entities::canvas, query::find_canvas};
+
+#[cfg(not(any(feature = "sqlite", feature = "postgres", feature = "mysql")))]
+compile_error!("at least one of the `sqlite`, `postgres` or `mysql` features must be enabled");
+
+pub const DEFAULT_DATABASE_URL: &str = "sqlite:./data.db?mode=rwc";
+
+/// The database to use, taken from `DATABASE_URL` (the same variable
+/// `sea-orm-cli` reads), defaults to `./data.db`.
+pub fn database_url() -> String {
+  std::env::var("DATABASE_URL").unwrap_or_else(|_| DEFAULT_DATABASE_URL.to_owned())
+}
+
+pub async fn connect() -> DatabaseConnection {
+  Database::connect(database_url())
+    .await
+    .expect("Error opening database!")
+}
+
+pub enum SchemaError {
+  Db(DbErr),
+  /// Migrations known to this build but not applied yet.
+  Pending(Vec<String>),
+  /// Migrations applied to the database but unknown to this build.
+  Newer(Vec<String>),
+}
+
+impl Display for SchemaError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      SchemaError::Db(err) => write!(f, "Error checking schema: {err}"),
+      SchemaError::Pending(names) => write!(
+        f,
+        "Database is not migrated, pending migrations: {}",
+        names.join(", ")
+      ),
+      SchemaError::Newer(names) => write!(
+        f,
+        "Database schema is newer than this build, unknown migrations: {}",
+        names.join(", ")
+      ),
+    }
+  }
+}
+
+impl From<DbErr> for SchemaError {
+  fn from(err: DbErr) -> Self {
+    SchemaError::Db(err)
+  }
+}
+
+/// Check that the schema matches this build, applying pending
+/// migrations first if `migrate` is set.
+pub async fn check_schema(db: &DatabaseConnection, migrate: bool) -> Result<(), SchemaError> {
+  let known = Migrator::migrations()
+    .iter()
+    .map(|migration| migration.name().to_owned())
+    .collect::<Vec<_>>();
+
+  let applied = Migrator::get_migration_models(db)
+    .await?
+    .into_iter()
+    .map(|model| model.version)
+    .collect::<Vec<_>>();
+
+  let newer = applied
+    .iter()
+    .filter(|version| !known.contains(version))
+    .cloned()
+    .collect::<Vec<_>>();
+
+  if !newer.is_empty() {
+    return Err(SchemaError::Newer(newer));
+  }
+
+  let pending = known
+    .into_iter()
+    .filter(|name| !applied.contains(name))
+    .collect::<Vec<_>>();
+
+  if pending.is_empty() {
+    return Ok(());
+  }
+
+  if !migrate {
+    return Err(SchemaError::Pending(pending));
+  }
+
+  Migrator::up(db, None).await?;
+
+  Ok(())
+}
+
+#[derive(clap::Args)]
+pub struct DbArgs {
+  /// Do not apply pending migrations on startup
+  #[arg(long)]
+  pub no_migrate: bool,
+}
+
+impl DbArgs {
+  /// Connect to the database and make sure the schema is up to date,
+  /// exits if it is not.
+  pub async fn connect(&self) -> DatabaseConnection {
+    let db = connect().await;
+
+    if let Err(err) = check_schema(&db, !self.no_migrate).await {
+      eprintln!("{err}");
+      std::process::exit(1);
+    }
+
+    db
+  }
+}
+
+#[derive(clap::Args)]
+pub struct CanvasArgs {
+  /// Name of the canvas
+  #[arg(long, default_value_t = String::from(DEFAULT_CANVAS))]
+  pub canvas: String,
+}
+
+impl CanvasArgs {
+  /// Fetch the chosen canvas, exits if it does not exist.
+  pub async fn find(&self, db: &DatabaseConnection) -> canvas::Model {
+    let canvas = find_canvas(db, &self.canvas)
+      .await
+      .expect("Error fetching canvas!");
+
+    match canvas {
+      Some(canvas) => canvas,
+      None => {
+        eprintln!("No such canvas: {}", self.canvas);
+        std::process::exit(1);
+      }
+    }
+  }
+}
@@ -0,0 +1,254 @@
+use chrono::{DateTime, Duration, Local};
+use sea_orm::{ActiveModelTrait, ActiveValue, DatabaseConnection, DbErr};
+use serde::Serialize;
+
+use crate::entities::bot_flag;
+
+// TODO(config)
+/// Fewer paints say nothing about a user.
+pub const MIN_SAMPLES: usize = 50;
+/// Users scoring at least this are flagged to moderators.
+pub const FLAG_SCORE: f64 = 0.7;
+/// A pause this long ends a painting session.
+const SESSION_GAP: i64 = 30 * 60;
+
+/// One paint, as far as detection is concerned.
+#[derive(Clone, Copy, Debug)]
+pub struct Sample {
+  pub time: DateTime<Local>,
+  pub x: i32,
+  pub y: i32,
+}
+
+/// How bot-like a series of paints looks.
+#[derive(Clone, Debug, Serialize)]
+pub struct Verdict {
+  pub samples: usize,
+  /// Standard deviation over mean of the intervals between paints,
+  /// scripts sleeping a fixed time are close to `0`.
+  pub interval_cv: f64,
+  /// Share of paints taking the same step as the one before, scripts
+  /// filling in raster order are close to `1`.
+  pub stride_ratio: f64,
+  /// Seconds of the longest painting session.
+  pub session: i64,
+  /// From `0` to `1`.
+  pub score: f64,
+  pub reasons: Vec<&'static str>,
+}
+
+/// `0` at `low`, `1` at `high` and linear between.
+fn ramp(value: f64, low: f64, high: f64) -> f64 {
+  ((value - low) / (high - low)).clamp(0.0, 1.0)
+}
+
+fn interval_cv(samples: &[Sample]) -> f64 {
+  let intervals = samples
+    .windows(2)
+    .map(|pair| (pair[1].time - pair[0].time).num_milliseconds() as f64)
+    .collect::<Vec<_>>();
+
+  let mean = intervals.iter().sum::<f64>() / intervals.len() as f64;
+
+  if mean <= 0.0 {
+    return 0.0;
+  }
+
+  let variance = intervals
+    .iter()
+    .map(|item| (item - mean).powi(2))
+    .sum::<f64>()
+    / intervals.len() as f64;
+
+  variance.sqrt() / mean
+}
+
+fn stride_ratio(samples: &[Sample]) -> f64 {
+  let steps = samples
+    .windows(2)
+    .map(|pair| (pair[1].x - pair[0].x, pair[1].y - pair[0].y))
+    .collect::<Vec<_>>();
+
+  let same = steps.windows(2).filter(|pair| pair[0] == pair[1]).count();
+
+  same as f64 / (steps.len() - 1) as f64
+}
+
+/// The longest run of paints without a pause of [`SESSION_GAP`], for
+/// samples in order.
+pub fn longest_session(samples: &[Sample]) -> Duration {
+  let mut longest = Duration::zero();
+  let mut start = match samples.first() {
+    Some(sample) => sample.time,
+    None => return longest,
+  };
+
+  for pair in samples.windows(2) {
+    if (pair[1].time - pair[0].time).num_seconds() > SESSION_GAP {
+      start = pair[1].time;
+    }
+
+    longest = longest.max(pair[1].time - start);
+  }
+
+  longest
+}
+
+/// Score paints of one user in order, `session` being how long they
+/// have been painting. `None` for fewer than [`MIN_SAMPLES`].
+pub fn score(samples: &[Sample], session: Duration) -> Option<Verdict> {
+  if samples.len() < MIN_SAMPLES {
+    return None;
+  }
+
+  let interval_cv = interval_cv(samples);
+  let stride_ratio = stride_ratio(samples);
+
+  // TODO(config)
+  let timing = 1.0 - ramp(interval_cv, 0.05, 0.5);
+  let spatial = ramp(stride_ratio, 0.5, 0.9);
+  let length = ramp(session.num_minutes() as f64 / 60.0, 4.0, 12.0);
+
+  let mut reasons = vec![];
+
+  if timing >= 0.5 {
+    reasons.push("regular timing");
+  }
+  if spatial >= 0.5 {
+    reasons.push("raster order");
+  }
+  if length >= 0.5 {
+    reasons.push("long session");
+  }
+
+  Some(Verdict {
+    samples: samples.len(),
+    interval_cv,
+    stride_ratio,
+    session: session.num_seconds(),
+    score: 0.4 * timing + 0.4 * spatial + 0.2 * length,
+    reasons,
+  })
+}
+
+/// Record a flag for moderators, `source` telling what raised it.
+pub async fn save_flag(
+  db: &DatabaseConnection,
+  uid: i32,
+  canvas_id: i32,
+  source: &str,
+  verdict: &Verdict,
+) -> Result<(), DbErr> {
+  let flag = bot_flag::ActiveModel {
+    uid: ActiveValue::set(uid),
+    canvas_id: ActiveValue::set(canvas_id),
+    source: ActiveValue::set(source.to_owned()),
+    score: ActiveValue::set(verdict.score),
+    details: ActiveValue::set(serde_json::to_string(verdict).unwrap()),
+    time: ActiveValue::set(Local::now()),
+    ..Default::default()
+  };
+
+  flag.insert(db).await?;
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::TimeZone;
+
+  use super::*;
+
+  /// `num` paints a fixed `interval` apart, left to right.
+  fn script(num: usize, interval: i64) -> Vec<Sample> {
+    let start = Local.timestamp_opt(1_675_000_000, 0).unwrap();
+
+    (0..num)
+      .map(|idx| Sample {
+        time: start + Duration::milliseconds(interval * idx as i64),
+        x: idx as i32 % 100,
+        y: idx as i32 / 100,
+      })
+      .collect()
+  }
+
+  /// `num` paints at uneven intervals, all over the board.
+  fn human(num: usize) -> Vec<Sample> {
+    let mut time = Local.timestamp_opt(1_675_000_000, 0).unwrap();
+    let mut seed = 42u64;
+
+    (0..num)
+      .map(|_| {
+        seed = seed
+          .wrapping_mul(6364136223846793005)
+          .wrapping_add(1442695040888963407);
+        let rand = (seed >> 33) as i64;
+
+        time += Duration::milliseconds(300 + rand % 20_000);
+
+        Sample {
+          time,
+          x: (rand % 1000) as i32,
+          y: (rand / 1000 % 600) as i32,
+        }
+      })
+      .collect()
+  }
+
+  #[test]
+  fn steady_script_is_flagged() {
+    let samples = script(60, 1000);
+    let verdict = score(&samples, longest_session(&samples)).unwrap();
+
+    assert!(verdict.score >= FLAG_SCORE, "{verdict:?}");
+    assert_eq!(verdict.reasons, ["regular timing", "raster order"]);
+  }
+
+  #[test]
+  fn irregular_burst_is_not_flagged() {
+    let samples = human(60);
+    let verdict = score(&samples, longest_session(&samples)).unwrap();
+
+    assert!(verdict.score < FLAG_SCORE, "{verdict:?}");
+    assert!(verdict.reasons.is_empty(), "{verdict:?}");
+  }
+
+  #[test]
+  fn too_few_samples_are_not_scored() {
+    let samples = script(MIN_SAMPLES, 1000);
+
+    assert!(score(&samples[1..], Duration::zero()).is_none());
+    assert!(score(&samples, Duration::zero()).is_some());
+  }
+
+  #[test]
+  fn steady_timing_alone_stays_below_threshold() {
+    // regular but not in raster order, nor for long
+    let mut samples = human(60);
+    let start = samples[0].time;
+
+    for (idx, sample) in samples.iter_mut().enumerate() {
+      sample.time = start + Duration::seconds(idx as i64);
+    }
+
+    let verdict = score(&samples, longest_session(&samples)).unwrap();
+
+    assert!(verdict.score < FLAG_SCORE, "{verdict:?}");
+    assert_eq!(verdict.reasons, ["regular timing"]);
+
+    // nor when kept up for twelve hours
+    let verdict = score(&samples, Duration::hours(12)).unwrap();
+
+    assert!(verdict.score < FLAG_SCORE, "{verdict:?}");
+  }
+
+  #[test]
+  fn sessions_end_at_long_pauses() {
+    let mut samples = script(4, 60_000);
+    samples[2].time += Duration::hours(1);
+    samples[3].time += Duration::hours(1);
+
+    assert_eq!(longest_session(&samples), Duration::minutes(1));
+  }
+}
@@ -5,11 +5,13 @@ use sea_orm::entity::prelude::*;
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
 #[sea_orm(table_name = "board")]
 pub struct Model {
+  #[sea_orm(primary_key, auto_increment = false)]
+  pub canvas_id: i32,
   #[sea_orm(primary_key, auto_increment = false)]
   pub x: i32,
   #[sea_orm(primary_key, auto_increment = false)]
   pub y: i32,
-  pub color: String,
+  pub color: i32,
   pub uid: i32,
   pub time: DateTimeLocal,
 }
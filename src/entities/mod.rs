@@ -2,5 +2,15 @@
 
 pub mod prelude;
 
+pub mod audit_log;
+pub mod ban;
 pub mod board;
+pub mod bot_flag;
+pub mod canvas;
+pub mod event;
 pub mod paint;
+pub mod region;
+pub mod session_log;
+pub mod shadow_paint;
+pub mod shadowban;
+pub mod snapshot;
@@ -2,6 +2,24 @@
 
 use sea_orm::entity::prelude::*;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(16))")]
+pub enum PaintKind {
+  /// Painted by the user.
+  #[sea_orm(string_value = "paint")]
+  Paint,
+  /// The user undid their last paint, putting back the previous pixel
+  /// along with its owner.
+  #[sea_orm(string_value = "undo")]
+  Undo,
+  /// Put back by a moderator rolling back another user.
+  #[sea_orm(string_value = "rollback")]
+  Rollback,
+  /// Restored by a moderator to how it looked at some point.
+  #[sea_orm(string_value = "restore")]
+  Restore,
+}
+
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
 #[sea_orm(table_name = "paint")]
 pub struct Model {
@@ -9,9 +27,11 @@ pub struct Model {
   pub id: i32,
   pub x: i32,
   pub y: i32,
-  pub color: String,
+  pub color: i32,
   pub uid: i32,
   pub time: DateTimeLocal,
+  pub canvas_id: i32,
+  pub kind: PaintKind,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -1,4 +1,14 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.10.7
 
+pub use super::audit_log::Entity as AuditLog;
+pub use super::ban::Entity as Ban;
 pub use super::board::Entity as Board;
+pub use super::bot_flag::Entity as BotFlag;
+pub use super::canvas::Entity as Canvas;
+pub use super::event::Entity as Event;
 pub use super::paint::Entity as Paint;
+pub use super::region::Entity as Region;
+pub use super::session_log::Entity as SessionLog;
+pub use super::shadow_paint::Entity as ShadowPaint;
+pub use super::shadowban::Entity as Shadowban;
+pub use super::snapshot::Entity as Snapshot;
@@ -0,0 +1,31 @@
+use std::sync::Arc;
+
+use crate::AppState;
+
+/// Freeze the board on `SIGUSR1` and unfreeze it on `SIGUSR2`, for
+/// maintenance without an admin token.
+#[cfg(unix)]
+#[tracing::instrument(skip_all)]
+pub async fn freeze_signal(state: Arc<AppState>) {
+  use tokio::signal::unix::{signal, SignalKind};
+
+  let mut freeze = signal(SignalKind::user_defined1()).expect("Error installing SIGUSR1 handler");
+  let mut unfreeze = signal(SignalKind::user_defined2()).expect("Error installing SIGUSR2 handler");
+
+  loop {
+    let frozen = tokio::select! {
+      _ = freeze.recv() => true,
+      _ = unfreeze.recv() => false,
+      _ = state.stopping() => break,
+    };
+
+    if state.set_frozen(frozen) {
+      tracing::info!(frozen, "Freeze state changed by signal.");
+    }
+  }
+}
+
+#[cfg(not(unix))]
+pub async fn freeze_signal(state: Arc<AppState>) {
+  state.stopping().await;
+}
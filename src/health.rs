@@ -0,0 +1,74 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, Json};
+use chrono::Local;
+use sea_orm::DbErr;
+use serde::Serialize;
+
+use crate::AppState;
+
+// TODO(config)
+const MAX_FAILURES: u32 = 3;
+
+#[derive(Default, Serialize)]
+pub struct SaveHealth {
+  failures: u32,
+  last_error: Option<String>,
+  last_success: Option<String>,
+}
+
+impl SaveHealth {
+  pub fn success(&mut self) {
+    self.failures = 0;
+    self.last_success = Some(Local::now().to_rfc3339());
+  }
+
+  /// Record a failed save, returns the number of consecutive failures.
+  pub fn failure(&mut self, err: &DbErr) -> u32 {
+    self.failures += 1;
+    self.last_error = Some(err.to_string());
+    self.failures
+  }
+}
+
+#[derive(Default, Serialize)]
+pub struct Health {
+  pub board: SaveHealth,
+  pub actions: SaveHealth,
+}
+
+#[derive(Serialize)]
+pub struct HealthReport<'a> {
+  healthy: bool,
+  dirty_pixels: usize,
+  pending_actions: usize,
+  #[serde(flatten)]
+  health: &'a Health,
+}
+
+pub async fn health(State(state): State<Arc<AppState>>) -> (StatusCode, Json<serde_json::Value>) {
+  let dirty_pixels = state
+    .canvases
+    .values()
+    .map(|canvas| canvas.dirty.lock().len())
+    .sum();
+  let pending_actions = state.actions.lock().len();
+
+  let health = state.health.lock();
+  let healthy = health.board.failures < MAX_FAILURES && health.actions.failures < MAX_FAILURES;
+
+  let report = HealthReport {
+    healthy,
+    dirty_pixels,
+    pending_actions,
+    health: &health,
+  };
+
+  let code = if healthy {
+    StatusCode::OK
+  } else {
+    StatusCode::SERVICE_UNAVAILABLE
+  };
+
+  (code, Json(serde_json::to_value(report).unwrap()))
+}
@@ -0,0 +1,112 @@
+use std::sync::Arc;
+
+use axum::{
+  extract::{Query, State},
+  http::StatusCode,
+  Json,
+};
+use chrono::{DateTime, Local};
+use sea_orm::ActiveEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::AppState;
+use yur_paintboard::query::{find_canvas, paints_page, PaintFilter, Rect};
+
+// TODO(config)
+const MAX_PAGE_SIZE: u64 = 1000;
+
+#[derive(Deserialize)]
+pub struct HistoryQuery {
+  canvas: Option<String>,
+  x0: Option<u16>,
+  y0: Option<u16>,
+  x1: Option<u16>,
+  y1: Option<u16>,
+  uid: Option<i32>,
+  /// RFC 3339
+  since: Option<String>,
+  /// RFC 3339
+  until: Option<String>,
+  #[serde(default)]
+  page: u64,
+  page_size: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct PaintInfo {
+  id: i32,
+  x: i32,
+  y: i32,
+  color: i32,
+  uid: i32,
+  time: String,
+  /// `paint`, `undo` for a pixel the user put back by undoing, or
+  /// `rollback` and `restore` for pixels put back by a moderator.
+  kind: String,
+}
+
+pub fn parse_time(time: &Option<String>) -> Result<Option<DateTime<Local>>, StatusCode> {
+  match time {
+    Some(time) => DateTime::parse_from_rfc3339(time)
+      .map(|time| Some(time.with_timezone(&Local)))
+      .map_err(|_| StatusCode::BAD_REQUEST),
+    None => Ok(None),
+  }
+}
+
+/// `GET /paints`, one page of the paint history.
+pub async fn history(
+  State(state): State<Arc<AppState>>,
+  Query(query): Query<HistoryQuery>,
+) -> Result<Json<Vec<PaintInfo>>, StatusCode> {
+  let rect = match (query.x0, query.y0, query.x1, query.y1) {
+    (Some(x0), Some(y0), Some(x1), Some(y1)) => Some(Rect { x0, y0, x1, y1 }),
+    (None, None, None, None) => None,
+    _ => return Err(StatusCode::BAD_REQUEST),
+  };
+
+  // archived canvases are not loaded, so look them up in the database
+  let canvas_id = match &query.canvas {
+    Some(name) => match find_canvas(&state.db, name).await.map_err(|err| {
+      tracing::error!("Query canvas failed: {err}");
+      StatusCode::INTERNAL_SERVER_ERROR
+    })? {
+      Some(canvas) => Some(canvas.id),
+      None => return Err(StatusCode::NOT_FOUND),
+    },
+    None => None,
+  };
+
+  let filter = PaintFilter {
+    canvas_id,
+    rect,
+    uid: query.uid,
+    kind: None,
+    since: parse_time(&query.since)?,
+    until: parse_time(&query.until)?,
+  };
+
+  let page_size = query.page_size.unwrap_or(100).clamp(1, MAX_PAGE_SIZE);
+
+  let paints = paints_page(&state.db, &filter, query.page, page_size)
+    .await
+    .map_err(|err| {
+      tracing::error!("Query paints failed: {err}");
+      StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+  let paints = paints
+    .into_iter()
+    .map(|paint| PaintInfo {
+      id: paint.id,
+      x: paint.x,
+      y: paint.y,
+      color: paint.color,
+      uid: paint.uid,
+      time: paint.time.to_rfc3339(),
+      kind: paint.kind.to_value(),
+    })
+    .collect();
+
+  Ok(Json(paints))
+}
@@ -0,0 +1,531 @@
+use std::{
+  fs::{self, File, OpenOptions},
+  io::{self, Write},
+  path::{Path, PathBuf},
+  sync::{atomic::Ordering, Arc},
+  time::Duration,
+};
+
+use chrono::{DateTime, Local, TimeZone};
+use parking_lot::Mutex;
+use sea_orm::ActiveValue;
+
+use crate::{
+  save::{persist, persist_paints},
+  AppState,
+};
+use yur_paintboard::{
+  entities::paint::{self, PaintKind},
+  pixel::color_to_int,
+  query::previous_paint,
+};
+
+/// Written at the start of every segment, followed by [`VERSION`].
+const MAGIC: &[u8; 4] = b"YURJ";
+/// Version 2 records the canvas of each paint, version 3 its kind.
+const VERSION: u8 = 3;
+const HEADER_LEN: usize = MAGIC.len() + 1;
+const ENTRY_LEN: usize = 24;
+/// Entries of version 2, read as [`PaintKind::Paint`].
+const V2_ENTRY_LEN: usize = 23;
+
+/// Segments without a header come from version 1, before canvases.
+const LEGACY_ENTRY_LEN: usize = 19;
+/// Their paints belong to `main`, created first by the canvas migration.
+const LEGACY_CANVAS_ID: i32 = 1;
+
+/// An accepted paint, as recorded in the journal.
+pub struct Entry {
+  pub canvas_id: i32,
+  pub x: u16,
+  pub y: u16,
+  pub color: (u8, u8, u8),
+  pub uid: i32,
+  pub time: DateTime<Local>,
+  pub kind: PaintKind,
+}
+
+fn kind_to_byte(kind: PaintKind) -> u8 {
+  match kind {
+    PaintKind::Paint => 0,
+    PaintKind::Undo => 1,
+    PaintKind::Rollback => 2,
+    PaintKind::Restore => 3,
+  }
+}
+
+fn kind_from_byte(byte: u8) -> io::Result<PaintKind> {
+  match byte {
+    0 => Ok(PaintKind::Paint),
+    1 => Ok(PaintKind::Undo),
+    2 => Ok(PaintKind::Rollback),
+    3 => Ok(PaintKind::Restore),
+    _ => Err(io::Error::new(
+      io::ErrorKind::InvalidData,
+      format!("unknown paint kind {byte}"),
+    )),
+  }
+}
+
+impl From<&Entry> for [u8; ENTRY_LEN] {
+  fn from(entry: &Entry) -> Self {
+    let mut res = [0; ENTRY_LEN];
+
+    res[0..2].copy_from_slice(&entry.x.to_le_bytes());
+    res[2..4].copy_from_slice(&entry.y.to_le_bytes());
+
+    let c = entry.color;
+    res[4..7].copy_from_slice(&[c.0, c.1, c.2]);
+
+    res[7..11].copy_from_slice(&entry.uid.to_le_bytes());
+    res[11..19].copy_from_slice(&entry.time.timestamp_nanos().to_le_bytes());
+    res[19..23].copy_from_slice(&entry.canvas_id.to_le_bytes());
+    res[23] = kind_to_byte(entry.kind);
+
+    res
+  }
+}
+
+impl Entry {
+  /// Read an entry of version 2 or 3, depending on the length of `data`.
+  fn parse(data: &[u8]) -> io::Result<Self> {
+    let nanos = i64::from_le_bytes(data[11..19].try_into().unwrap());
+
+    let kind = match data.get(23) {
+      Some(byte) => kind_from_byte(*byte)?,
+      None => PaintKind::Paint,
+    };
+
+    Ok(Entry {
+      canvas_id: i32::from_le_bytes(data[19..23].try_into().unwrap()),
+      x: u16::from_le_bytes([data[0], data[1]]),
+      y: u16::from_le_bytes([data[2], data[3]]),
+      color: (data[4], data[5], data[6]),
+      uid: i32::from_le_bytes(data[7..11].try_into().unwrap()),
+      time: Local.timestamp_nanos(nanos),
+      kind,
+    })
+  }
+
+  /// Read an entry of a version 1 segment.
+  fn parse_legacy(data: &[u8]) -> io::Result<Self> {
+    let mut full = [0; V2_ENTRY_LEN];
+    full[..LEGACY_ENTRY_LEN].copy_from_slice(data);
+    full[LEGACY_ENTRY_LEN..].copy_from_slice(&LEGACY_CANVAS_ID.to_le_bytes());
+
+    Entry::parse(&full)
+  }
+}
+
+/// Read the entries of a segment, a torn entry at the end is dropped.
+fn parse_segment(data: &[u8]) -> io::Result<Vec<Entry>> {
+  if data.len() < HEADER_LEN {
+    // empty, or torn while writing the header
+    return Ok(vec![]);
+  }
+
+  if !data.starts_with(MAGIC) {
+    return data
+      .chunks_exact(LEGACY_ENTRY_LEN)
+      .map(Entry::parse_legacy)
+      .collect();
+  }
+
+  let entry_len = match data[MAGIC.len()] {
+    2 => V2_ENTRY_LEN,
+    VERSION => ENTRY_LEN,
+    version => {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("unsupported journal version {version}"),
+      ))
+    }
+  };
+
+  data[HEADER_LEN..]
+    .chunks_exact(entry_len)
+    .map(Entry::parse)
+    .collect()
+}
+
+/// Entries left from the previous run.
+pub struct Leftover {
+  /// Already inserted as paints, but maybe not saved to the board.
+  pub saved: Vec<Entry>,
+  pub unsaved: Vec<Entry>,
+}
+
+/// Append-only log of accepted paints, split into numbered segments.
+///
+/// Entries are buffered by [`Journal::append`] and written + fsynced in
+/// batches by [`sync_journal`]. Segments older than the one returned by
+/// [`Journal::rotate`] are marked by [`Journal::mark_saved`] once their
+/// paints are in the database, and can be removed once the board is too.
+pub struct Journal {
+  dir: PathBuf,
+  buf: Mutex<Vec<u8>>,
+  file: Mutex<(u64, File)>,
+}
+
+fn segment_path(dir: &Path, seq: u64) -> PathBuf {
+  dir.join(format!("{seq:020}.log"))
+}
+
+fn list_segments(dir: &Path) -> io::Result<Vec<u64>> {
+  let mut segments = vec![];
+
+  for item in fs::read_dir(dir)? {
+    let path = item?.path();
+
+    if path.extension().is_some_and(|ext| ext == "log") {
+      if let Some(seq) = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| stem.parse().ok())
+      {
+        segments.push(seq);
+      }
+    }
+  }
+
+  segments.sort_unstable();
+
+  Ok(segments)
+}
+
+fn saved_path(dir: &Path) -> PathBuf {
+  dir.join("saved")
+}
+
+/// Segments before this one only hold paints already in the database.
+fn read_saved(dir: &Path) -> io::Result<u64> {
+  match fs::read_to_string(saved_path(dir)) {
+    Ok(data) => data
+      .trim()
+      .parse()
+      .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+    Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(0),
+    Err(err) => Err(err),
+  }
+}
+
+fn open_segment(dir: &Path, seq: u64) -> io::Result<File> {
+  let mut file = OpenOptions::new()
+    .create(true)
+    .append(true)
+    .open(segment_path(dir, seq))?;
+
+  if file.metadata()?.len() == 0 {
+    file.write_all(MAGIC)?;
+    file.write_all(&[VERSION])?;
+    file.sync_data()?;
+  }
+
+  Ok(file)
+}
+
+impl Journal {
+  /// Open the journal in `dir`, returns it along with all entries left
+  /// from the previous run.
+  pub fn open(dir: impl Into<PathBuf>) -> io::Result<(Self, Leftover)> {
+    let dir = dir.into();
+    fs::create_dir_all(&dir)?;
+
+    let segments = list_segments(&dir)?;
+    let saved = read_saved(&dir)?;
+    let mut leftover = Leftover {
+      saved: vec![],
+      unsaved: vec![],
+    };
+
+    for seq in &segments {
+      let data = fs::read(segment_path(&dir, *seq))?;
+
+      let entries = if *seq < saved {
+        &mut leftover.saved
+      } else {
+        &mut leftover.unsaved
+      };
+
+      entries.extend(parse_segment(&data)?);
+    }
+
+    let seq = segments.last().map_or(0, |seq| seq + 1);
+    let file = open_segment(&dir, seq)?;
+
+    let journal = Journal {
+      dir,
+      buf: Mutex::new(vec![]),
+      file: Mutex::new((seq, file)),
+    };
+
+    Ok((journal, leftover))
+  }
+
+  pub fn append(&self, entry: &Entry) {
+    let entry: [u8; ENTRY_LEN] = entry.into();
+    self.buf.lock().extend_from_slice(&entry);
+  }
+
+  fn write_out(&self, file: &mut File) -> io::Result<()> {
+    let buf = std::mem::take(&mut *self.buf.lock());
+
+    if buf.is_empty() {
+      return Ok(());
+    }
+
+    file.write_all(&buf)?;
+    file.sync_data()
+  }
+
+  /// Write buffered entries to the current segment and fsync it.
+  pub fn sync(&self) -> io::Result<()> {
+    let mut file = self.file.lock();
+    self.write_out(&mut file.1)
+  }
+
+  /// Sync and start a new segment, returns its sequence number.
+  pub fn rotate(&self) -> io::Result<u64> {
+    let mut file = self.file.lock();
+    self.write_out(&mut file.1)?;
+
+    let seq = file.0 + 1;
+    *file = (seq, open_segment(&self.dir, seq)?);
+
+    Ok(seq)
+  }
+
+  /// Record that the paints of all segments before `seq` are in the
+  /// database, so that they are not inserted again on replay.
+  pub fn mark_saved(&self, seq: u64) -> io::Result<()> {
+    let path = saved_path(&self.dir);
+    let tmp = path.with_extension("tmp");
+
+    let mut file = File::create(&tmp)?;
+    file.write_all(seq.to_string().as_bytes())?;
+    file.sync_data()?;
+
+    fs::rename(tmp, path)
+  }
+
+  /// Remove all segments before `seq`.
+  pub fn remove_before(&self, seq: u64) -> io::Result<()> {
+    for item in list_segments(&self.dir)? {
+      if item < seq {
+        fs::remove_file(segment_path(&self.dir, item))?;
+      }
+    }
+
+    Ok(())
+  }
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn sync_journal(state: Arc<AppState>) {
+  // TODO(config)
+  let mut interval = tokio::time::interval(Duration::from_millis(100));
+
+  loop {
+    tokio::select! {
+      _ = interval.tick() => { },
+      _ = state.stopping() => break,
+    }
+
+    let state = state.clone();
+    let res = tokio::task::spawn_blocking(move || state.journal.sync()).await;
+
+    if let Ok(Err(err)) = res {
+      tracing::error!("Sync journal failed: {err}");
+    }
+  }
+}
+
+/// Insert entries left from the previous run not saved yet into the
+/// database, then apply all of them to the board.
+#[tracing::instrument(skip_all)]
+pub async fn replay_journal(state: &AppState, leftover: Leftover) {
+  tracing::info!(
+    saved = leftover.saved.len(),
+    unsaved = leftover.unsaved.len(),
+    "Replaying journal..."
+  );
+
+  let actions = leftover
+    .unsaved
+    .iter()
+    .map(|entry| paint::ActiveModel {
+      x: ActiveValue::set(entry.x.into()),
+      y: ActiveValue::set(entry.y.into()),
+      color: ActiveValue::set(color_to_int(entry.color)),
+      uid: ActiveValue::set(entry.uid),
+      time: ActiveValue::set(entry.time),
+      canvas_id: ActiveValue::set(entry.canvas_id),
+      kind: ActiveValue::set(entry.kind),
+      ..Default::default()
+    })
+    .collect::<Vec<_>>();
+
+  state.actions.lock().extend(actions);
+
+  // undos are replayed from the paint log, which must be complete
+  if let Err(err) = persist_paints(state).await {
+    // kept in the queue and the journal until `save_actions` succeeds
+    tracing::error!("Save replayed paints failed: {err}");
+  }
+
+  for entry in leftover.saved.iter().chain(&leftover.unsaved) {
+    apply_entry(state, entry).await;
+  }
+
+  match persist(state).await {
+    Ok(()) => tracing::info!("Replay journal success!"),
+    Err(err) => tracing::error!("Save replayed journal failed: {err}"),
+  }
+}
+
+/// Apply `entry` to the board unless the pixel was painted later.
+async fn apply_entry(state: &AppState, entry: &Entry) {
+  let canvas = match state.canvas_by_id(entry.canvas_id) {
+    Some(canvas) => canvas,
+    None => return,
+  };
+
+  let (x, y) = (i32::from(entry.x), i32::from(entry.y));
+
+  let (uid, time) = if entry.kind == PaintKind::Undo {
+    // back to the pixel as it was before the undone paint
+    match previous_paint(&state.db, canvas.id, x, y, entry.time, 1, |_| false).await {
+      Ok(Some(paint)) => (paint.uid, paint.time),
+      Ok(None) => (-1, Local.timestamp_opt(0, 0).unwrap()),
+      Err(err) => {
+        tracing::error!(x, y, "Replay undo failed: {err}");
+        return;
+      }
+    }
+  } else {
+    (entry.uid, entry.time)
+  };
+
+  if let Some(pixel) = canvas.board.read().get(&(entry.x, entry.y)) {
+    let mut pixel = pixel.lock();
+
+    if entry.time >= pixel.time {
+      pixel.color = color_to_int(entry.color);
+      pixel.uid = uid;
+      pixel.time = time;
+
+      canvas.dirty.lock().insert((entry.x, entry.y));
+      canvas.painted.store(true, Ordering::Relaxed);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn entry(kind: PaintKind) -> Entry {
+    Entry {
+      canvas_id: 7,
+      x: 999,
+      y: 598,
+      color: (0x12, 0x34, 0x56),
+      uid: 114514,
+      time: Local.timestamp_nanos(1_675_000_000_123_456_789),
+      kind,
+    }
+  }
+
+  fn fields(entry: &Entry) -> (i32, u16, u16, (u8, u8, u8), i32, i64, PaintKind) {
+    (
+      entry.canvas_id,
+      entry.x,
+      entry.y,
+      entry.color,
+      entry.uid,
+      entry.time.timestamp_nanos(),
+      entry.kind,
+    )
+  }
+
+  fn segment(version: u8, entries: &[&[u8]]) -> Vec<u8> {
+    let mut data = MAGIC.to_vec();
+    data.push(version);
+
+    for entry in entries {
+      data.extend_from_slice(entry);
+    }
+
+    data
+  }
+
+  #[test]
+  fn round_trip() {
+    for kind in [
+      PaintKind::Paint,
+      PaintKind::Undo,
+      PaintKind::Rollback,
+      PaintKind::Restore,
+    ] {
+      let entry = entry(kind);
+      let data: [u8; ENTRY_LEN] = (&entry).into();
+
+      let parsed = parse_segment(&segment(VERSION, &[&data])).unwrap();
+
+      assert_eq!(parsed.len(), 1);
+      assert_eq!(fields(&parsed[0]), fields(&entry));
+    }
+  }
+
+  #[test]
+  fn version_2_is_paint() {
+    let entry = entry(PaintKind::Undo);
+    let data: [u8; ENTRY_LEN] = (&entry).into();
+
+    let parsed = parse_segment(&segment(2, &[&data[..V2_ENTRY_LEN]])).unwrap();
+
+    assert_eq!(parsed.len(), 1);
+    assert_eq!(parsed[0].kind, PaintKind::Paint);
+    assert_eq!(parsed[0].canvas_id, 7);
+    assert_eq!(parsed[0].uid, 114514);
+  }
+
+  #[test]
+  fn version_1_is_main() {
+    let entry = entry(PaintKind::Paint);
+    let data: [u8; ENTRY_LEN] = (&entry).into();
+
+    // no header, two entries
+    let mut segment = data[..LEGACY_ENTRY_LEN].to_vec();
+    segment.extend_from_slice(&data[..LEGACY_ENTRY_LEN]);
+
+    let parsed = parse_segment(&segment).unwrap();
+
+    assert_eq!(parsed.len(), 2);
+    assert_eq!(parsed[1].canvas_id, LEGACY_CANVAS_ID);
+    assert_eq!(parsed[1].x, 999);
+    assert_eq!(parsed[1].time, entry.time);
+  }
+
+  #[test]
+  fn torn_entry_is_dropped() {
+    let data: [u8; ENTRY_LEN] = (&entry(PaintKind::Paint)).into();
+
+    let parsed = parse_segment(&segment(VERSION, &[&data, &data[..10]])).unwrap();
+    assert_eq!(parsed.len(), 1);
+
+    // torn while writing the header
+    assert!(parse_segment(&MAGIC[..3]).unwrap().is_empty());
+    assert!(parse_segment(&segment(VERSION, &[])).unwrap().is_empty());
+  }
+
+  #[test]
+  fn unknown_version_or_kind_fails() {
+    let mut data: [u8; ENTRY_LEN] = (&entry(PaintKind::Paint)).into();
+
+    assert!(parse_segment(&segment(VERSION + 1, &[&data])).is_err());
+
+    data[23] = 0xff;
+    assert!(parse_segment(&segment(VERSION, &[&data])).is_err());
+  }
+}
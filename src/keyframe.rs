@@ -0,0 +1,43 @@
+use std::{
+  sync::{atomic::Ordering, Arc},
+  time::Duration,
+};
+
+use chrono::Local;
+
+use crate::AppState;
+use yur_paintboard::replay::save_keyframe;
+
+// TODO(config)
+// 10 minutes
+const KEYFRAME_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Store a keyframe of every canvas painted on since the last one, so
+/// that rebuilding a past board only replays the tail of the log.
+#[tracing::instrument(skip_all)]
+pub async fn save_keyframes(state: Arc<AppState>) {
+  loop {
+    tokio::select! {
+      _ = tokio::time::sleep(KEYFRAME_INTERVAL) => { },
+      _ = state.stopping() => break,
+    }
+
+    for canvas in state.canvases.values() {
+      if !canvas.painted.swap(false, Ordering::Relaxed) {
+        continue;
+      }
+
+      let time = Local::now();
+      let frame = canvas.frame();
+
+      if let Err(err) = save_keyframe(&state.db, canvas.id, time, &frame).await {
+        tracing::error!(canvas = canvas.name, "Save keyframe failed: {err}");
+
+        // try again next time
+        canvas.painted.store(true, Ordering::Relaxed);
+      } else {
+        tracing::info!(canvas = canvas.name, "Saved keyframe.");
+      }
+    }
+  }
+}
@@ -1,3 +1,10 @@
+pub mod board;
+pub mod board_file;
 pub mod consts;
+pub mod db;
+pub mod detect;
 pub mod entities;
 pub mod pixel;
+pub mod query;
+pub mod region;
+pub mod replay;
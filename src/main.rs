@@ -1,34 +1,328 @@
+mod admin;
+mod audit;
+mod auth;
+mod bots;
+mod canvas;
+mod freeze;
+mod health;
+mod history;
+mod journal;
+mod keyframe;
+mod moderation;
 mod save;
+mod session;
 mod ws;
 
-use std::{collections::HashMap, sync::Arc};
+use std::{
+  collections::{HashMap, HashSet},
+  net::SocketAddr,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  time::Duration,
+};
 
-use axum::{routing::get, Router};
+use axum::{
+  routing::{get, post},
+  Router,
+};
 use chrono::{DateTime, Local};
+use clap::Parser;
 use jsonwebtoken::DecodingKey;
 use parking_lot::Mutex;
-use sea_orm::{Database, DatabaseConnection, EntityTrait};
-use tokio::sync::broadcast::{self, Sender};
+use sea_orm::{ActiveValue, DatabaseConnection, EntityTrait};
+use tokio::sync::{broadcast, watch};
 
 use tracing_subscriber::{filter, prelude::*};
 
-use crate::save::{save_actions, save_board};
+use crate::{
+  bots::BotAction,
+  canvas::CanvasState,
+  freeze::freeze_signal,
+  health::Health,
+  journal::{replay_journal, sync_journal, Entry, Journal},
+  keyframe::save_keyframes,
+  save::{persist, save_actions, save_board},
+  session::{Notice, Sessions},
+};
 use yur_paintboard::{
-  entities::{board, paint, prelude::*},
-  pixel::Pixel,
+  consts::DEFAULT_CANVAS,
+  db::DbArgs,
+  entities::{
+    ban, board,
+    event::EventStatus,
+    paint::{self, PaintKind},
+    prelude::*,
+    shadow_paint,
+  },
+  pixel::{int_to_bin, Pixel},
 };
 
 pub struct AppState {
   pubkey: DecodingKey,
   db: DatabaseConnection,
-  sender: Sender<Pixel>,
-  board: HashMap<(u16, u16), Mutex<board::Model>>,
-  user_paint: Mutex<HashMap<i32, DateTime<Local>>>,
+  canvases: HashMap<String, CanvasState>,
+  /// The canvas served on `/ws`.
+  default_canvas: String,
   actions: Mutex<Vec<paint::ActiveModel>>,
+  journal: Journal,
+  /// Held while persisting or writing the board, so that journal segments
+  /// are dropped in order and only once the board is saved.
+  persisting: tokio::sync::Mutex<()>,
+  /// Held while growing a canvas, so that the database and memory agree.
+  resizing: tokio::sync::Mutex<()>,
+  health: Mutex<Health>,
+  /// Banned users, a copy of the `ban` table.
+  bans: Mutex<HashMap<i32, ban::Model>>,
+  /// Shadowbanned users, a copy of the `shadowban` table.
+  shadowbans: Mutex<HashSet<i32>>,
+  /// Paints of shadowbanned users waiting to be saved for review.
+  shadow_actions: Mutex<Vec<shadow_paint::ActiveModel>>,
+  sessions: Sessions,
+  /// Paints are refused on all canvases while set.
+  frozen: AtomicBool,
+  bot_action: BotAction,
+  /// Whether undoing a paint also gives back the cooldown it took.
+  undo_refund: bool,
+  /// Users who must solve a captcha, with the time they were asked to.
+  challenged: Mutex<HashMap<i32, DateTime<Local>>>,
+  notices: broadcast::Sender<Notice>,
+  shutdown: watch::Sender<bool>,
+}
+
+impl AppState {
+  fn canvas_by_id(&self, id: i32) -> Option<&CanvasState> {
+    self.canvases.values().find(|canvas| canvas.id == id)
+  }
+
+  /// Record the paint of a shadowbanned user and echo it to their own
+  /// sessions only, leaving the board untouched.
+  fn apply_shadow_paint(&self, canvas: &CanvasState, new_pixel: board::Model) {
+    let x = new_pixel.x as u16;
+    let y = new_pixel.y as u16;
+    let [r, g, b] = int_to_bin(new_pixel.color);
+
+    let new_action = shadow_paint::ActiveModel {
+      canvas_id: ActiveValue::set(canvas.id),
+      x: ActiveValue::set(new_pixel.x),
+      y: ActiveValue::set(new_pixel.y),
+      color: ActiveValue::set(new_pixel.color),
+      uid: ActiveValue::set(new_pixel.uid),
+      time: ActiveValue::set(new_pixel.time),
+      ..Default::default()
+    };
+
+    self.shadow_actions.lock().push(new_action);
+
+    let pixel = Pixel {
+      x,
+      y,
+      color: (r, g, b),
+    };
+
+    // fails only if nobody is listening
+    let _ = canvas.shadow_sender.send((new_pixel.uid, pixel));
+  }
+
+  /// Freeze or unfreeze the board and tell every client.
+  ///
+  /// Returns whether the state changed.
+  fn set_frozen(&self, frozen: bool) -> bool {
+    if self.frozen.swap(frozen, Ordering::Relaxed) == frozen {
+      return false;
+    }
+
+    // fails only if nobody is listening
+    let _ = self.notices.send(Notice::Freeze(frozen));
+
+    true
+  }
+
+  /// Resolves once the server is shutting down.
+  async fn stopping(&self) {
+    let mut shutdown = self.shutdown.subscribe();
+
+    while !*shutdown.borrow() {
+      if shutdown.changed().await.is_err() {
+        return;
+      }
+    }
+  }
+
+  /// The ban of `uid` in force at `now`, if any.
+  fn ban_of(&self, uid: i32, now: DateTime<Local>) -> Option<ban::Model> {
+    self
+      .bans
+      .lock()
+      .get(&uid)
+      .filter(|ban| ban.until.is_none_or(|until| now < until))
+      .cloned()
+  }
+
+  /// Put `new_pixel` on `canvas` if `only_if` holds for the current
+  /// pixel, then log it as a paint of `kind` and broadcast the change.
+  ///
+  /// Returns whether it was applied.
+  fn apply_paint(
+    &self,
+    canvas: &CanvasState,
+    new_pixel: board::Model,
+    kind: PaintKind,
+    only_if: impl FnOnce(&board::Model) -> bool,
+  ) -> bool {
+    let entry = Entry {
+      canvas_id: canvas.id,
+      x: new_pixel.x as u16,
+      y: new_pixel.y as u16,
+      color: int_to_bin(new_pixel.color).into(),
+      uid: new_pixel.uid,
+      time: new_pixel.time,
+      kind,
+    };
+
+    self.apply(canvas, new_pixel, entry, only_if)
+  }
+
+  /// Put `previous` back on `canvas` if `only_if` holds for the current
+  /// pixel, logged as an undo by `uid` at `time`, and broadcast the
+  /// change.
+  ///
+  /// Returns whether it was applied.
+  fn apply_undo(
+    &self,
+    canvas: &CanvasState,
+    previous: board::Model,
+    uid: i32,
+    time: DateTime<Local>,
+    only_if: impl FnOnce(&board::Model) -> bool,
+  ) -> bool {
+    let entry = Entry {
+      canvas_id: canvas.id,
+      x: previous.x as u16,
+      y: previous.y as u16,
+      color: int_to_bin(previous.color).into(),
+      uid,
+      time,
+      kind: PaintKind::Undo,
+    };
+
+    self.apply(canvas, previous, entry, only_if)
+  }
+
+  /// Put `new_pixel` on `canvas` if `only_if` holds for the current
+  /// pixel, logging `entry` as the paint.
+  fn apply(
+    &self,
+    canvas: &CanvasState,
+    new_pixel: board::Model,
+    entry: Entry,
+    only_if: impl FnOnce(&board::Model) -> bool,
+  ) -> bool {
+    let (x, y, color) = (entry.x, entry.y, entry.color);
+
+    let new_action = paint::ActiveModel {
+      x: ActiveValue::set(x.into()),
+      y: ActiveValue::set(y.into()),
+      color: ActiveValue::set(new_pixel.color),
+      uid: ActiveValue::set(entry.uid),
+      time: ActiveValue::set(entry.time),
+      canvas_id: ActiveValue::set(canvas.id),
+      kind: ActiveValue::set(entry.kind),
+      ..Default::default()
+    };
+
+    let same = {
+      let board = canvas.board.read();
+      let mut pixel = board.get(&(x, y)).unwrap().lock();
+
+      if !only_if(&pixel) {
+        return false;
+      }
+
+      // same color
+      let same = pixel.color == new_pixel.color;
+
+      *pixel = new_pixel;
+
+      same
+    };
+
+    canvas.dirty.lock().insert((x, y));
+    canvas.painted.store(true, Ordering::Relaxed);
+
+    {
+      let mut actions = self.actions.lock();
+
+      self.journal.append(&entry);
+
+      actions.push(new_action);
+    }
+
+    if !same {
+      // fails only if nobody is listening
+      let _ = canvas.sender.send(Pixel { x, y, color });
+    }
+
+    true
+  }
+}
+
+#[derive(Parser)]
+#[command(name = "yur-paintboard")]
+#[command(author = "yurzhang")]
+#[command(about = "Just paint freely!")]
+#[command(version, long_about = None)]
+struct Args {
+  /// Seconds to wait for the final save on shutdown
+  #[arg(long, default_value_t = 60)]
+  shutdown_timeout: u64,
+
+  /// Fill pixels missing from the `board` table with the background
+  /// instead of refusing to start
+  #[arg(long)]
+  fill_missing: bool,
+
+  /// What to do with users the online bot detection flags
+  #[arg(long, value_enum, default_value_t = BotAction::None)]
+  bot_action: BotAction,
+
+  /// Give back the cooldown of paints undone by their painter
+  #[arg(long)]
+  undo_refund: bool,
+
+  #[command(flatten)]
+  db: DbArgs,
+}
+
+async fn shutdown_signal() {
+  let ctrl_c = async {
+    tokio::signal::ctrl_c()
+      .await
+      .expect("Error installing Ctrl-C handler");
+  };
+
+  #[cfg(unix)]
+  let terminate = async {
+    tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+      .expect("Error installing SIGTERM handler")
+      .recv()
+      .await;
+  };
+
+  #[cfg(not(unix))]
+  let terminate = std::future::pending::<()>();
+
+  tokio::select! {
+    _ = ctrl_c => { },
+    _ = terminate => { },
+  }
 }
 
 #[tokio::main]
 async fn main() {
+  let args = Args::parse();
+
   let target_layer = filter::Targets::new()
     .with_target("sqlx", tracing::Level::ERROR)
     .with_target("yur_paintboard", tracing::Level::INFO);
@@ -51,49 +345,177 @@ async fn main() {
 
   let pubkey = DecodingKey::from_ed_pem(&pubkey).expect("Error loading public key");
 
-  let db = Database::connect("sqlite:./data.db?mode=rwc")
+  let db = args.db.connect().await;
+
+  let models = Canvas::find()
+    .all(&db)
     .await
-    .expect("Error opening database!");
+    .expect("Error fetching canvases!");
 
-  let board = Board::find().all(&db).await.expect("Error fetching board!");
+  let events = Event::find()
+    .all(&db)
+    .await
+    .expect("Error fetching events!");
 
-  // TODO(config)
-  let (sender, _) = broadcast::channel::<Pixel>(65536);
+  let mut canvases = HashMap::new();
+  let mut default_canvas = DEFAULT_CANVAS.to_owned();
+
+  for model in models {
+    let event = events.iter().find(|event| event.canvas_id == model.id);
 
-  let mut now_board = HashMap::new();
-  let mut old_board = HashMap::new();
+    if let Some(event) = event {
+      match event.status {
+        EventStatus::Archived => continue,
+        EventStatus::Active => default_canvas = model.name.clone(),
+        _ => {}
+      }
+    }
 
-  for pixel in board {
-    let pos = (pixel.x as u16, pixel.y as u16);
-    now_board.insert(pos, Mutex::new(pixel.clone()));
-    old_board.insert(pos, pixel);
+    let canvas = match CanvasState::load(&db, model, event, args.fill_missing).await {
+      Ok(canvas) => canvas,
+      Err(err) => {
+        eprintln!("{err}");
+        std::process::exit(1);
+      }
+    };
+
+    tracing::info!(canvas = canvas.name, "Loaded canvas.");
+
+    canvases.insert(canvas.name.clone(), canvas);
   }
 
+  let bans = Ban::find()
+    .all(&db)
+    .await
+    .expect("Error fetching bans!")
+    .into_iter()
+    .map(|ban| (ban.uid, ban))
+    .collect();
+
+  let shadowbans = Shadowban::find()
+    .all(&db)
+    .await
+    .expect("Error fetching shadowbans!")
+    .into_iter()
+    .map(|shadowban| shadowban.uid)
+    .collect();
+
+  // TODO(config)
+  let (journal, leftover) = Journal::open("./journal").expect("Error opening journal!");
+
   let init_state = AppState {
     pubkey,
     db,
-    sender,
-    board: now_board,
-    user_paint: Mutex::new(HashMap::new()),
+    canvases,
+    default_canvas,
     actions: Mutex::new(vec![]),
+    journal,
+    persisting: tokio::sync::Mutex::new(()),
+    resizing: tokio::sync::Mutex::new(()),
+    health: Mutex::new(Health::default()),
+    bans: Mutex::new(bans),
+    shadowbans: Mutex::new(shadowbans),
+    shadow_actions: Mutex::new(vec![]),
+    sessions: Sessions::default(),
+    frozen: AtomicBool::new(false),
+    bot_action: args.bot_action,
+    undo_refund: args.undo_refund,
+    challenged: Mutex::new(HashMap::new()),
+    // TODO(config)
+    notices: broadcast::channel(16).0,
+    shutdown: watch::channel(false).0,
   };
   let shared_state = Arc::new(init_state);
 
+  if !leftover.saved.is_empty() || !leftover.unsaved.is_empty() {
+    replay_journal(&shared_state, leftover).await;
+  }
+
   let app = Router::new()
     .route("/", get(|| async { "Just paint freely!" }))
     .route("/ws", get(ws::ws))
+    .route("/ws/:canvas", get(ws::ws_canvas))
+    .route("/health", get(health::health))
+    .route("/paints", get(history::history))
+    .route("/admin/rollback", post(admin::admin_rollback))
+    .route("/admin/restore", post(admin::admin_restore))
+    .route("/admin/ban", post(admin::admin_ban))
+    .route("/admin/unban", post(admin::admin_unban))
+    .route("/admin/announce", post(admin::admin_announce))
+    .route("/admin/sessions", get(admin::admin_sessions))
+    .route("/admin/freeze", post(admin::admin_freeze))
+    .route("/admin/shadowban", post(admin::admin_shadowban))
+    .route("/admin/unshadowban", post(admin::admin_unshadowban))
+    .route("/admin/flags", get(admin::admin_flags))
+    .route("/admin/resize", post(admin::admin_resize))
     .with_state(shared_state.clone());
 
+  let signal_state = shared_state.clone();
+
   // TODO(config)
-  let web_task =
-    axum::Server::bind(&"127.0.0.1:2895".parse().unwrap()).serve(app.into_make_service());
+  let web_task = axum::Server::bind(&"127.0.0.1:2895".parse().unwrap())
+    .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+    .with_graceful_shutdown(async move {
+      shutdown_signal().await;
 
-  let save_board_task = save_board(shared_state.clone(), old_board);
-  let save_actions_task = save_actions(shared_state);
+      tracing::info!("Shutting down...");
+
+      // tell all sockets to close
+      signal_state.shutdown.send_replace(true);
+    });
+
+  let save_board_task = save_board(shared_state.clone());
+  let save_actions_task = save_actions(shared_state.clone());
+  let sync_journal_task = sync_journal(shared_state.clone());
+  let save_keyframes_task = save_keyframes(shared_state.clone());
+  let freeze_signal_task = freeze_signal(shared_state.clone());
 
   tracing::info!("Listening on 127.0.0.1:2895...");
 
-  let (res, _, _) = futures::future::join3(web_task, save_board_task, save_actions_task).await;
+  let web_task = async {
+    let res = web_task.await;
+
+    // also stop the loops if the server failed
+    shared_state.shutdown.send_replace(true);
+
+    res
+  };
 
+  // the loops finish their current save and stop, so that nothing they
+  // took out of the queues is lost
+  let (res, _) = tokio::join!(
+    web_task,
+    futures::future::join5(
+      save_board_task,
+      save_actions_task,
+      sync_journal_task,
+      save_keyframes_task,
+      freeze_signal_task,
+    ),
+  );
   res.unwrap();
+
+  let timeout = Duration::from_secs(args.shutdown_timeout);
+
+  tracing::info!("Waiting for sessions to close...");
+
+  // sockets may still accept paints until they are closed
+  if tokio::time::timeout(timeout, shared_state.sessions.drained())
+    .await
+    .is_err()
+  {
+    tracing::warn!("Sessions did not close in time");
+  }
+
+  if let Err(err) = shared_state.journal.sync() {
+    tracing::error!("Sync journal failed: {err}");
+  }
+
+  tracing::info!("Saving before exit...");
+
+  match tokio::time::timeout(timeout, persist(&shared_state)).await {
+    Ok(Ok(())) => tracing::info!("Saved, bye!"),
+    Ok(Err(err)) => tracing::error!("Final save failed, the journal is kept: {err}"),
+    Err(_) => tracing::error!("Final save timed out, the journal is kept"),
+  }
 }
@@ -0,0 +1,126 @@
+use chrono::{DateTime, Local};
+use sea_orm::{DbErr, EntityTrait};
+
+use crate::{canvas::CanvasState, save::persist, AppState};
+use yur_paintboard::{
+  entities::{board, paint::PaintKind, prelude::*},
+  query::{previous_paint, Rect},
+  replay::board_at,
+};
+
+/// Undo the pixels `target` currently owns on `canvas`, only those
+/// painted in `since..=until` if given.
+///
+/// Each pixel goes back to the color of the latest paint before it that
+/// is not one of the undone ones, or the background if there is none, as
+/// paints by `moderator`. Returns the number of pixels restored.
+pub async fn rollback(
+  state: &AppState,
+  canvas: &CanvasState,
+  target: i32,
+  since: Option<DateTime<Local>>,
+  until: Option<DateTime<Local>>,
+  moderator: i32,
+) -> Result<usize, DbErr> {
+  // the history must be complete in the database
+  persist(state).await?;
+
+  let in_range = |time: &DateTime<Local>| {
+    since.is_none_or(|since| *time >= since) && until.is_none_or(|until| *time <= until)
+  };
+
+  let targets = canvas
+    .board
+    .read()
+    .values()
+    .map(|pixel| pixel.lock().clone())
+    .filter(|pixel| pixel.uid == target && in_range(&pixel.time))
+    .collect::<Vec<_>>();
+
+  let now = Local::now();
+  let mut restored = 0;
+
+  for old_pixel in targets {
+    // the paints being rolled back are skipped like undone ones
+    let previous = previous_paint(
+      &state.db,
+      canvas.id,
+      old_pixel.x,
+      old_pixel.y,
+      old_pixel.time,
+      0,
+      |paint| paint.uid == target && in_range(&paint.time),
+    )
+    .await?;
+
+    let color = match previous {
+      Some(paint) => paint.color,
+      None => canvas.background,
+    };
+
+    let new_pixel = board::Model {
+      color,
+      uid: moderator,
+      time: now,
+      ..old_pixel.clone()
+    };
+
+    // skip pixels painted over in the meantime
+    let applied = state.apply_paint(canvas, new_pixel, PaintKind::Rollback, |pixel| {
+      pixel.uid == old_pixel.uid && pixel.time == old_pixel.time
+    });
+
+    if applied {
+      restored += 1;
+    }
+  }
+
+  Ok(restored)
+}
+
+/// Restore `rect` of `canvas` to how it looked at `time`, as paints by
+/// `moderator`. Returns the number of pixels changed.
+pub async fn restore_rect(
+  state: &AppState,
+  canvas: &CanvasState,
+  rect: Rect,
+  time: DateTime<Local>,
+  moderator: i32,
+) -> Result<usize, DbErr> {
+  // the history must be complete in the database
+  persist(state).await?;
+
+  let model = Canvas::find_by_id(canvas.id)
+    .one(&state.db)
+    .await?
+    .ok_or_else(|| DbErr::RecordNotFound(format!("canvas {}", canvas.name)))?;
+
+  let frame = board_at(&state.db, &model, time).await?;
+
+  let now = Local::now();
+  let mut restored = 0;
+
+  for x in rect.x0..rect.x1.min(canvas.width()) {
+    for y in rect.y0..rect.y1.min(canvas.height()) {
+      let color = frame.get(x, y).unwrap().color;
+
+      let new_pixel = board::Model {
+        canvas_id: canvas.id,
+        x: x.into(),
+        y: y.into(),
+        color,
+        uid: moderator,
+        time: now,
+      };
+
+      // only diffs, pixels already right are left alone
+      if state.apply_paint(canvas, new_pixel, PaintKind::Restore, |pixel| {
+        pixel.color != color
+      }) {
+        restored += 1;
+      }
+    }
+  }
+
+  Ok(restored)
+}
@@ -19,8 +19,51 @@ impl From<&Pixel> for [u8; 7] {
   }
 }
 
-pub fn color_to_hex(color: (u8, u8, u8)) -> String {
-  format!("#{:02X}{:02X}{:02X}", color.0, color.1, color.2)
+/// Pack a color into `0xRRGGBB`, the way it is stored in the database.
+pub fn color_to_int(color: (u8, u8, u8)) -> i32 {
+  i32::from_be_bytes([0, color.0, color.1, color.2])
+}
+
+pub fn int_to_bin(color: i32) -> [u8; 3] {
+  let [_, r, g, b] = color.to_be_bytes();
+
+  [r, g, b]
+}
+
+/// Whether `color` is in the form of `#RRGGBB`.
+pub fn check_color(color: &str) -> bool {
+  if color.len() != 7 {
+    return false;
+  }
+
+  if !color.starts_with('#') {
+    return false;
+  }
+
+  for c in color.chars().skip(1) {
+    if !c.is_ascii_hexdigit() {
+      return false;
+    }
+  }
+
+  true
+}
+
+/// Parse a comma separated list of `#RRGGBB` into packed colors.
+pub fn parse_palette(palette: &str) -> Option<Vec<i32>> {
+  palette
+    .split(',')
+    .map(|color| {
+      let color = color.trim();
+
+      if !check_color(color) {
+        return None;
+      }
+
+      let [r, g, b] = hex_to_bin(color);
+      Some(color_to_int((r, g, b)))
+    })
+    .collect()
 }
 
 pub fn hex_to_bin(hex: &str) -> [u8; 3] {
@@ -0,0 +1,263 @@
+use futures::{Stream, TryStreamExt};
+use sea_orm::{entity::prelude::*, DatabaseConnection, QueryOrder, Select};
+
+use crate::entities::{
+  canvas,
+  event::{self, EventStatus},
+  paint::{self, PaintKind},
+  prelude::*,
+};
+
+/// A rectangle on the board, `x0..x1` × `y0..y1`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+  pub x0: u16,
+  pub y0: u16,
+  pub x1: u16,
+  pub y1: u16,
+}
+
+impl Rect {
+  pub fn contains(&self, x: u16, y: u16) -> bool {
+    self.x0 <= x && x < self.x1 && self.y0 <= y && y < self.y1
+  }
+}
+
+/// Conditions on the paint history, all of them optional.
+#[derive(Clone, Debug, Default)]
+pub struct PaintFilter {
+  pub canvas_id: Option<i32>,
+  pub rect: Option<Rect>,
+  pub uid: Option<i32>,
+  /// Set to [`PaintKind::Paint`] to leave out the paints not made by
+  /// their users, when looking at how users paint.
+  pub kind: Option<PaintKind>,
+  /// Inclusive.
+  pub since: Option<DateTimeLocal>,
+  /// Exclusive.
+  pub until: Option<DateTimeLocal>,
+}
+
+impl PaintFilter {
+  /// Paints matching the filter, in the order they were made.
+  pub fn select(&self) -> Select<Paint> {
+    let mut select = Paint::find();
+
+    if let Some(canvas_id) = self.canvas_id {
+      select = select.filter(paint::Column::CanvasId.eq(canvas_id));
+    }
+
+    if let Some(rect) = self.rect {
+      select = select
+        .filter(paint::Column::X.gte(rect.x0))
+        .filter(paint::Column::X.lt(rect.x1))
+        .filter(paint::Column::Y.gte(rect.y0))
+        .filter(paint::Column::Y.lt(rect.y1));
+    }
+
+    if let Some(uid) = self.uid {
+      select = select.filter(paint::Column::Uid.eq(uid));
+    }
+
+    if let Some(kind) = self.kind {
+      select = select.filter(paint::Column::Kind.eq(kind));
+    }
+
+    if let Some(since) = self.since {
+      select = select.filter(paint::Column::Time.gte(since));
+    }
+
+    if let Some(until) = self.until {
+      select = select.filter(paint::Column::Time.lt(until));
+    }
+
+    select
+      .order_by_asc(paint::Column::Time)
+      .order_by_asc(paint::Column::Id)
+  }
+}
+
+pub async fn find_canvas(
+  db: &DatabaseConnection,
+  name: &str,
+) -> Result<Option<canvas::Model>, DbErr> {
+  Canvas::find()
+    .filter(canvas::Column::Name.eq(name))
+    .one(db)
+    .await
+}
+
+pub async fn find_event(
+  db: &DatabaseConnection,
+  name: &str,
+) -> Result<Option<event::Model>, DbErr> {
+  Event::find()
+    .filter(event::Column::Name.eq(name))
+    .one(db)
+    .await
+}
+
+/// The event currently served on `/ws`, if any.
+pub async fn active_event(db: &DatabaseConnection) -> Result<Option<event::Model>, DbErr> {
+  Event::find()
+    .filter(event::Column::Status.eq(EventStatus::Active))
+    .one(db)
+    .await
+}
+
+/// Fetch the `page`-th page (from 0) of paints matching `filter`.
+pub async fn paints_page(
+  db: &DatabaseConnection,
+  filter: &PaintFilter,
+  page: u64,
+  page_size: u64,
+) -> Result<Vec<paint::Model>, DbErr> {
+  filter
+    .select()
+    .paginate(db, page_size)
+    .fetch_page(page)
+    .await
+}
+
+/// Stream all paints matching `filter` without loading them into memory.
+pub async fn stream_paints<'a>(
+  db: &'a DatabaseConnection,
+  filter: &PaintFilter,
+) -> Result<impl Stream<Item = Result<paint::Model, DbErr>> + Send + 'a, DbErr> {
+  filter.select().stream(db).await
+}
+
+/// The paint pixel `x`, `y` of `canvas_id` showed right before `time`, if
+/// any, leaving out those `skip` holds for.
+///
+/// An undo cancels the paint it undid, and `pending` more paints are
+/// cancelled first, `1` to look before the paint undone at `time`.
+pub async fn previous_paint(
+  db: &DatabaseConnection,
+  canvas_id: i32,
+  x: i32,
+  y: i32,
+  time: DateTimeLocal,
+  mut pending: usize,
+  skip: impl Fn(&paint::Model) -> bool,
+) -> Result<Option<paint::Model>, DbErr> {
+  let paints = Paint::find()
+    .filter(paint::Column::CanvasId.eq(canvas_id))
+    .filter(paint::Column::X.eq(x))
+    .filter(paint::Column::Y.eq(y))
+    .filter(paint::Column::Time.lt(time))
+    .order_by_desc(paint::Column::Time)
+    .order_by_desc(paint::Column::Id)
+    .stream(db)
+    .await?;
+  futures::pin_mut!(paints);
+
+  while let Some(paint) = paints.try_next().await? {
+    // undos only ever revert the latest paint of the pixel
+    if paint.kind == PaintKind::Undo {
+      pending += 1;
+      continue;
+    }
+
+    if pending > 0 {
+      pending -= 1;
+      continue;
+    }
+
+    if !skip(&paint) {
+      return Ok(Some(paint));
+    }
+  }
+
+  Ok(None)
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+  use chrono::{Local, TimeZone};
+  use migration::{Migrator, MigratorTrait};
+  use sea_orm::{ActiveModelTrait, ActiveValue, ConnectOptions, Database};
+
+  use super::*;
+
+  async fn memory_db() -> DatabaseConnection {
+    // one connection, every new one would open another empty database
+    let mut options = ConnectOptions::new("sqlite::memory:".to_owned());
+    options.max_connections(1);
+
+    let db = Database::connect(options).await.unwrap();
+    Migrator::up(&db, None).await.unwrap();
+
+    db
+  }
+
+  async fn insert(db: &DatabaseConnection, uid: i32, secs: i64, color: i32, kind: PaintKind) {
+    paint::ActiveModel {
+      x: ActiveValue::set(3),
+      y: ActiveValue::set(4),
+      color: ActiveValue::set(color),
+      uid: ActiveValue::set(uid),
+      time: ActiveValue::set(time(secs)),
+      canvas_id: ActiveValue::set(1),
+      kind: ActiveValue::set(kind),
+      ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap();
+  }
+
+  fn time(secs: i64) -> DateTimeLocal {
+    Local.timestamp_opt(1_675_000_000 + secs, 0).unwrap()
+  }
+
+  /// C paints, B paints over, A misclicks over B and undoes it.
+  async fn misclick_undone() -> DatabaseConnection {
+    let db = memory_db().await;
+
+    insert(&db, 3, 0, 0x0000ff, PaintKind::Paint).await;
+    insert(&db, 2, 10, 0x00ff00, PaintKind::Paint).await;
+    insert(&db, 1, 20, 0xff0000, PaintKind::Paint).await;
+    insert(&db, 1, 25, 0x00ff00, PaintKind::Undo).await;
+
+    db
+  }
+
+  #[tokio::test]
+  async fn undo_puts_back_the_paint_before() {
+    let db = misclick_undone().await;
+
+    let paint = previous_paint(&db, 1, 3, 4, time(25), 1, |_| false)
+      .await
+      .unwrap()
+      .unwrap();
+
+    assert_eq!((paint.uid, paint.color), (2, 0x00ff00));
+  }
+
+  #[tokio::test]
+  async fn rollback_after_undo_skips_the_undone_paint() {
+    let db = misclick_undone().await;
+
+    // from the time of the pixel, and from after the undo
+    for secs in [10, 30] {
+      let paint = previous_paint(&db, 1, 3, 4, time(secs), 0, |paint| paint.uid == 2)
+        .await
+        .unwrap()
+        .unwrap();
+
+      assert_eq!((paint.uid, paint.color), (3, 0x0000ff));
+    }
+  }
+
+  #[tokio::test]
+  async fn none_when_all_are_skipped() {
+    let db = misclick_undone().await;
+
+    let paint = previous_paint(&db, 1, 3, 4, time(10), 0, |paint| paint.uid != 2)
+      .await
+      .unwrap();
+
+    assert!(paint.is_none());
+  }
+}
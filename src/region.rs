@@ -0,0 +1,208 @@
+use chrono::{DateTime, Local};
+use image::RgbaImage;
+
+use crate::{entities::region, query::Rect};
+
+/// An area of a canvas only some users may paint, see the `region` table.
+#[derive(Clone, Debug)]
+pub struct ProtectedRegion {
+  pub name: String,
+  pub rect: Rect,
+  /// One bit per pixel of `rect`, column by column, protected if set.
+  /// The whole rectangle if `None`.
+  mask: Option<Vec<u8>>,
+  roles: Vec<String>,
+  uids: Vec<i32>,
+  starts_at: Option<DateTime<Local>>,
+  ends_at: Option<DateTime<Local>>,
+}
+
+fn mask_index(rect: &Rect, x: u16, y: u16) -> usize {
+  (x - rect.x0) as usize * (rect.y1 - rect.y0) as usize + (y - rect.y0) as usize
+}
+
+/// Parse a comma separated list, e.g. `1,2,3`.
+pub fn parse_list<T: std::str::FromStr>(list: &str) -> Option<Vec<T>> {
+  list
+    .split(',')
+    .map(str::trim)
+    .filter(|item| !item.is_empty())
+    .map(|item| item.parse().ok())
+    .collect()
+}
+
+impl ProtectedRegion {
+  /// `None` if the row is malformed.
+  pub fn from_model(model: region::Model) -> Option<Self> {
+    let rect = Rect {
+      x0: model.x0.try_into().ok()?,
+      y0: model.y0.try_into().ok()?,
+      x1: model.x1.try_into().ok()?,
+      y1: model.y1.try_into().ok()?,
+    };
+
+    if rect.x0 >= rect.x1 || rect.y0 >= rect.y1 {
+      return None;
+    }
+
+    let bits = (rect.x1 - rect.x0) as usize * (rect.y1 - rect.y0) as usize;
+
+    if let Some(mask) = &model.mask {
+      if mask.len() != bits.div_ceil(8) {
+        return None;
+      }
+    }
+
+    Some(ProtectedRegion {
+      name: model.name,
+      rect,
+      mask: model.mask,
+      roles: parse_list(model.roles.as_deref().unwrap_or(""))?,
+      uids: parse_list(model.uids.as_deref().unwrap_or(""))?,
+      starts_at: model.starts_at,
+      ends_at: model.ends_at,
+    })
+  }
+
+  /// Whether `(x, y)` is protected at `now`.
+  pub fn covers(&self, x: u16, y: u16, now: DateTime<Local>) -> bool {
+    if self.starts_at.is_some_and(|starts_at| now < starts_at)
+      || self.ends_at.is_some_and(|ends_at| now > ends_at)
+    {
+      return false;
+    }
+
+    if !self.rect.contains(x, y) {
+      return false;
+    }
+
+    match &self.mask {
+      Some(mask) => {
+        let idx = mask_index(&self.rect, x, y);
+        mask[idx / 8] & (1 << (idx % 8)) != 0
+      }
+      None => true,
+    }
+  }
+
+  /// Whether a user may paint inside.
+  pub fn allows(&self, uid: i32, role: Option<&str>) -> bool {
+    self.uids.contains(&uid) || role.is_some_and(|role| self.roles.iter().any(|item| item == role))
+  }
+}
+
+/// Build a mask over `rect` from an image of the same size, pixels that
+/// are not fully transparent are protected.
+pub fn mask_from_image(rect: &Rect, image: &RgbaImage) -> Option<Vec<u8>> {
+  let width = u32::from(rect.x1 - rect.x0);
+  let height = u32::from(rect.y1 - rect.y0);
+
+  if image.width() != width || image.height() != height {
+    return None;
+  }
+
+  let bits = image.width() as usize * image.height() as usize;
+  let mut mask = vec![0; bits.div_ceil(8)];
+
+  for (x, y, pixel) in image.enumerate_pixels() {
+    if pixel[3] != 0 {
+      let idx = mask_index(rect, rect.x0 + x as u16, rect.y0 + y as u16);
+      mask[idx / 8] |= 1 << (idx % 8);
+    }
+  }
+
+  Some(mask)
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::{Duration, TimeZone};
+  use image::Rgba;
+
+  use super::*;
+
+  fn now() -> DateTime<Local> {
+    Local.timestamp_opt(1_675_000_000, 0).unwrap()
+  }
+
+  /// A 3x2 region at `(10, 20)`.
+  fn model() -> region::Model {
+    region::Model {
+      id: 1,
+      canvas_id: 1,
+      name: "logo".to_owned(),
+      x0: 10,
+      y0: 20,
+      x1: 13,
+      y1: 22,
+      mask: None,
+      roles: Some("mod, artist".to_owned()),
+      uids: Some("7".to_owned()),
+      starts_at: None,
+      ends_at: None,
+    }
+  }
+
+  #[test]
+  fn rect_edges() {
+    let region = ProtectedRegion::from_model(model()).unwrap();
+
+    assert!(region.covers(10, 20, now()));
+    assert!(region.covers(12, 21, now()));
+    assert!(!region.covers(13, 21, now()));
+    assert!(!region.covers(12, 22, now()));
+    assert!(!region.covers(9, 20, now()));
+    assert!(!region.covers(10, 19, now()));
+  }
+
+  #[test]
+  fn masked_out_pixel() {
+    let mut model = model();
+    let rect = ProtectedRegion::from_model(model.clone()).unwrap().rect;
+    let mut image = RgbaImage::from_pixel(3, 2, Rgba([0, 0, 0, 255]));
+    image.put_pixel(1, 1, Rgba([0, 0, 0, 0]));
+    model.mask = mask_from_image(&rect, &image);
+
+    let region = ProtectedRegion::from_model(model).unwrap();
+
+    assert!(!region.covers(11, 21, now()));
+    assert!(region.covers(11, 20, now()));
+    assert!(region.covers(12, 21, now()));
+  }
+
+  #[test]
+  fn mask_must_match_rect() {
+    let mut model = model();
+    let rect = ProtectedRegion::from_model(model.clone()).unwrap().rect;
+    let image = RgbaImage::new(2, 3);
+
+    assert!(mask_from_image(&rect, &image).is_none());
+
+    model.mask = Some(vec![0; 2]);
+    assert!(ProtectedRegion::from_model(model).is_none());
+  }
+
+  #[test]
+  fn active_window() {
+    let mut model = model();
+    model.starts_at = Some(now());
+    model.ends_at = Some(now() + Duration::hours(1));
+
+    let region = ProtectedRegion::from_model(model).unwrap();
+
+    assert!(!region.covers(10, 20, now() - Duration::seconds(1)));
+    assert!(region.covers(10, 20, now()));
+    assert!(region.covers(10, 20, now() + Duration::hours(1)));
+    assert!(!region.covers(10, 20, now() + Duration::hours(1) + Duration::seconds(1)));
+  }
+
+  #[test]
+  fn exemptions() {
+    let region = ProtectedRegion::from_model(model()).unwrap();
+
+    assert!(region.allows(7, None));
+    assert!(region.allows(8, Some("artist")));
+    assert!(!region.allows(8, Some("admin")));
+    assert!(!region.allows(8, None));
+  }
+}
@@ -0,0 +1,384 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Local, TimeZone};
+use futures::TryStreamExt;
+use image::RgbImage;
+use sea_orm::{
+  sea_query::OnConflict, ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseConnection, DbErr,
+  EntityTrait, QueryFilter, QueryOrder, TransactionTrait,
+};
+
+use crate::{
+  board_file::remove_board_file,
+  entities::{
+    board, canvas,
+    paint::{self, PaintKind},
+    prelude::*,
+    snapshot,
+  },
+  pixel::{color_to_int, int_to_bin},
+  query::{previous_paint, PaintFilter},
+};
+
+/// The state of one pixel, as in the `board` table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cell {
+  pub color: i32,
+  /// `-1` if never painted.
+  pub uid: i32,
+  pub time: DateTime<Local>,
+}
+
+impl Cell {
+  /// A pixel never painted, in `background`.
+  pub fn blank(background: i32) -> Self {
+    Cell {
+      color: background,
+      uid: -1,
+      time: Local.timestamp_opt(0, 0).unwrap(),
+    }
+  }
+}
+
+impl From<&paint::Model> for Cell {
+  fn from(paint: &paint::Model) -> Self {
+    Cell {
+      color: paint.color,
+      uid: paint.uid,
+      time: paint.time,
+    }
+  }
+}
+
+const CELL_LEN: usize = 15;
+
+// TODO(config)
+/// Paints this close before a keyframe are replayed again, as they may
+/// have been accepted only after it was taken.
+const KEYFRAME_MARGIN: i64 = 10;
+
+/// A whole board in memory, rebuilt from the paint log.
+#[derive(Clone, Debug)]
+pub struct Frame {
+  pub width: u16,
+  pub height: u16,
+  cells: Vec<Cell>,
+}
+
+impl Frame {
+  /// A board of never painted pixels in `background`.
+  pub fn new(width: u16, height: u16, background: i32) -> Self {
+    let cell = Cell::blank(background);
+
+    Frame {
+      width,
+      height,
+      cells: vec![cell; width as usize * height as usize],
+    }
+  }
+
+  /// The board right after `setup`, painted with the background of `canvas`.
+  pub fn blank(canvas: &canvas::Model) -> Self {
+    Frame::new(canvas.width as u16, canvas.height as u16, canvas.background)
+  }
+
+  /// Copy into a blank board of `canvas`, in case it was resized.
+  pub fn fit(self, canvas: &canvas::Model) -> Self {
+    if self.width as i32 == canvas.width && self.height as i32 == canvas.height {
+      return self;
+    }
+
+    let mut frame = Frame::blank(canvas);
+
+    for ((x, y), cell) in self.iter() {
+      frame.set(x, y, *cell);
+    }
+
+    frame
+  }
+
+  fn index(&self, x: u16, y: u16) -> Option<usize> {
+    if x >= self.width || y >= self.height {
+      return None;
+    }
+
+    Some(x as usize * self.height as usize + y as usize)
+  }
+
+  pub fn get(&self, x: u16, y: u16) -> Option<&Cell> {
+    self.index(x, y).map(|idx| &self.cells[idx])
+  }
+
+  /// Pixels outside of the board are ignored.
+  pub fn set(&mut self, x: u16, y: u16, cell: Cell) {
+    if let Some(idx) = self.index(x, y) {
+      self.cells[idx] = cell;
+    }
+  }
+
+  /// Apply a paint, those outside of the board are ignored.
+  ///
+  /// Only the color of undos is right, see [`replay_paints`] for their
+  /// owner.
+  pub fn apply(&mut self, paint: &paint::Model) {
+    self.set(paint.x as u16, paint.y as u16, Cell::from(paint));
+  }
+
+  /// All pixels with their position, column by column.
+  pub fn iter(&self) -> impl Iterator<Item = ((u16, u16), &Cell)> + '_ {
+    let height = self.height as usize;
+
+    self
+      .cells
+      .iter()
+      .enumerate()
+      .map(move |(idx, cell)| (((idx / height) as u16, (idx % height) as u16), cell))
+  }
+
+  /// All cells, column by column, uncompressed.
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut data = Vec::with_capacity(self.cells.len() * CELL_LEN);
+
+    for cell in &self.cells {
+      data.extend_from_slice(&int_to_bin(cell.color));
+      data.extend_from_slice(&cell.uid.to_le_bytes());
+      data.extend_from_slice(&cell.time.timestamp_nanos().to_le_bytes());
+    }
+
+    data
+  }
+
+  /// The reverse of [`Frame::to_bytes`], `None` if the length is wrong.
+  pub fn from_bytes(width: u16, height: u16, data: &[u8]) -> Option<Self> {
+    if data.len() != width as usize * height as usize * CELL_LEN {
+      return None;
+    }
+
+    let cells = data
+      .chunks_exact(CELL_LEN)
+      .map(|cell| Cell {
+        color: color_to_int((cell[0], cell[1], cell[2])),
+        uid: i32::from_le_bytes(cell[3..7].try_into().unwrap()),
+        time: Local.timestamp_nanos(i64::from_le_bytes(cell[7..15].try_into().unwrap())),
+      })
+      .collect();
+
+    Some(Frame {
+      width,
+      height,
+      cells,
+    })
+  }
+
+  /// Compress into the format stored in the `snapshot` table.
+  pub fn encode(&self) -> Vec<u8> {
+    zstd::encode_all(self.to_bytes().as_slice(), 3).unwrap()
+  }
+
+  /// The reverse of [`Frame::encode`], `None` if `data` is corrupted.
+  pub fn decode(width: u16, height: u16, data: &[u8]) -> Option<Self> {
+    Frame::from_bytes(width, height, &zstd::decode_all(data).ok()?)
+  }
+
+  pub fn to_image(&self) -> RgbImage {
+    let mut imgbuf = RgbImage::new(self.width.into(), self.height.into());
+
+    for ((x, y), cell) in self.iter() {
+      *imgbuf.get_pixel_mut(x.into(), y.into()) = image::Rgb(int_to_bin(cell.color));
+    }
+
+    imgbuf
+  }
+}
+
+/// Store `frame` as the keyframe of `canvas_id` at `time`.
+pub async fn save_keyframe(
+  db: &DatabaseConnection,
+  canvas_id: i32,
+  time: DateTime<Local>,
+  frame: &Frame,
+) -> Result<(), DbErr> {
+  let keyframe = snapshot::ActiveModel {
+    canvas_id: ActiveValue::set(canvas_id),
+    time: ActiveValue::set(time),
+    width: ActiveValue::set(frame.width.into()),
+    height: ActiveValue::set(frame.height.into()),
+    data: ActiveValue::set(frame.encode()),
+    ..Default::default()
+  };
+
+  keyframe.insert(db).await?;
+
+  Ok(())
+}
+
+/// The latest keyframe of `canvas` taken at or before `time`, skipping
+/// corrupted ones.
+pub async fn nearest_keyframe(
+  db: &DatabaseConnection,
+  canvas: &canvas::Model,
+  time: DateTime<Local>,
+) -> Result<Option<(DateTime<Local>, Frame)>, DbErr> {
+  let keyframes = Snapshot::find()
+    .filter(snapshot::Column::CanvasId.eq(canvas.id))
+    .filter(snapshot::Column::Time.lte(time))
+    .order_by_desc(snapshot::Column::Time)
+    .stream(db)
+    .await?;
+  futures::pin_mut!(keyframes);
+
+  while let Some(keyframe) = keyframes.try_next().await? {
+    let frame = Frame::decode(
+      keyframe.width as u16,
+      keyframe.height as u16,
+      &keyframe.data,
+    );
+
+    match frame {
+      Some(frame) => return Ok(Some((keyframe.time, frame.fit(canvas)))),
+      None => tracing::warn!(id = keyframe.id, "Corrupted keyframe!"),
+    }
+  }
+
+  Ok(None)
+}
+
+/// Apply paints of `canvas` in `since..=until` to `frame` in order, undos
+/// putting back the pixel from before the paint they undid.
+pub async fn replay_paints(
+  db: &DatabaseConnection,
+  canvas: &canvas::Model,
+  frame: &mut Frame,
+  since: Option<DateTime<Local>>,
+  until: Option<DateTime<Local>>,
+) -> Result<(), DbErr> {
+  let filter = PaintFilter {
+    canvas_id: Some(canvas.id),
+    since,
+    ..Default::default()
+  };
+
+  let mut select = filter.select();
+
+  // `PaintFilter::until` is exclusive
+  if let Some(until) = until {
+    select = select.filter(paint::Column::Time.lte(until));
+  }
+
+  // undos need the paint log, look them up before streaming it
+  let undos = select
+    .clone()
+    .filter(paint::Column::Kind.eq(PaintKind::Undo))
+    .all(db)
+    .await?;
+
+  let mut undone = HashMap::with_capacity(undos.len());
+
+  for undo in undos {
+    // back to the pixel as it was before the undone paint
+    let cell = previous_paint(db, canvas.id, undo.x, undo.y, undo.time, 1, |_| false)
+      .await?
+      .map_or(Cell::blank(canvas.background), |paint| Cell::from(&paint));
+
+    undone.insert(undo.id, cell);
+  }
+
+  let paints = select.stream(db).await?;
+  futures::pin_mut!(paints);
+
+  while let Some(paint) = paints.try_next().await? {
+    match undone.get(&paint.id) {
+      Some(cell) => frame.set(paint.x as u16, paint.y as u16, *cell),
+      None => frame.apply(&paint),
+    }
+  }
+
+  Ok(())
+}
+
+/// Rebuild the board of `canvas` as of `time` (inclusive), starting from
+/// the nearest keyframe.
+pub async fn board_at(
+  db: &DatabaseConnection,
+  canvas: &canvas::Model,
+  time: DateTime<Local>,
+) -> Result<Frame, DbErr> {
+  let (mut frame, since) = match nearest_keyframe(db, canvas, time).await? {
+    Some((keyframe_time, frame)) => (
+      frame,
+      Some(keyframe_time - Duration::seconds(KEYFRAME_MARGIN)),
+    ),
+    None => (Frame::blank(canvas), None),
+  };
+
+  replay_paints(db, canvas, &mut frame, since, Some(time)).await?;
+
+  Ok(frame)
+}
+
+/// Fetch the `board` table of `canvas` as a frame, pixels missing
+/// from the table are left as background.
+pub async fn load_board(db: &DatabaseConnection, canvas: &canvas::Model) -> Result<Frame, DbErr> {
+  let mut frame = Frame::blank(canvas);
+
+  let pixels = Board::find()
+    .filter(board::Column::CanvasId.eq(canvas.id))
+    .all(db)
+    .await?;
+
+  for pixel in pixels {
+    let cell = Cell {
+      color: pixel.color,
+      uid: pixel.uid,
+      time: pixel.time,
+    };
+
+    frame.set(pixel.x as u16, pixel.y as u16, cell);
+  }
+
+  Ok(frame)
+}
+
+/// Overwrite the `board` table of `canvas` with `frame` in one transaction.
+///
+/// The server keeps the board in memory, so it must not be running.
+pub async fn restore_board(
+  db: &DatabaseConnection,
+  canvas: &canvas::Model,
+  frame: &Frame,
+) -> Result<(), DbErr> {
+  remove_board_file(&canvas.name)
+    .map_err(|err| DbErr::Custom(format!("Error removing board file: {err}")))?;
+
+  let tasks = frame
+    .iter()
+    .map(|((x, y), cell)| board::ActiveModel {
+      canvas_id: ActiveValue::set(canvas.id),
+      x: ActiveValue::set(x.into()),
+      y: ActiveValue::set(y.into()),
+      color: ActiveValue::set(cell.color),
+      uid: ActiveValue::set(cell.uid),
+      time: ActiveValue::set(cell.time),
+    })
+    .collect::<Vec<_>>();
+
+  let txn = db.begin().await?;
+
+  // TODO(config)
+  for task in tasks.chunks(600) {
+    Board::insert_many(task.to_owned())
+      .on_conflict(
+        OnConflict::columns([board::Column::CanvasId, board::Column::X, board::Column::Y])
+          .update_columns([
+            board::Column::Color,
+            board::Column::Uid,
+            board::Column::Time,
+          ])
+          .to_owned(),
+      )
+      .exec(&txn)
+      .await?;
+  }
+
+  txn.commit().await
+}
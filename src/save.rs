@@ -1,51 +1,200 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+  sync::{atomic::Ordering, Arc},
+  time::Duration,
+};
 
-use sea_orm::{sea_query::OnConflict, ActiveValue, EntityTrait};
+use sea_orm::{sea_query::OnConflict, ActiveValue, DbErr, EntityTrait, TransactionTrait};
 
-use crate::AppState;
-use yur_paintboard::entities::{board, paint, prelude::*};
+use crate::{canvas::CanvasState, AppState};
+use yur_paintboard::{
+  board_file::{last_paint_id, BoardFile},
+  entities::{board, paint, prelude::*},
+};
+
+// TODO(config)
+// 5 minutes
+const SAVE_BOARD_INTERVAL: Duration = Duration::from_secs(300);
+// 8 minutes
+const SAVE_ACTIONS_INTERVAL: Duration = Duration::from_secs(480);
+
+/// Delay before retrying after `failures` consecutive failed saves.
+fn backoff(failures: u32, interval: Duration) -> Duration {
+  // TODO(config)
+  let delay = Duration::from_secs(5) * 2u32.saturating_pow(failures.saturating_sub(1));
+  delay.min(interval)
+}
 
 #[tracing::instrument(skip_all)]
-pub async fn save_board(state: Arc<AppState>, mut old_board: HashMap<(u16, u16), board::Model>) {
+pub async fn save_board(state: Arc<AppState>) {
+  let mut delay = SAVE_BOARD_INTERVAL;
+
   loop {
-    // TODO(config)
-    // 5 minutes
-    tokio::time::sleep(std::time::Duration::from_secs(300)).await;
+    tokio::select! {
+      _ = tokio::time::sleep(delay) => { },
+      _ = state.stopping() => break,
+    }
 
     tracing::info!("Start saving board...");
 
-    let mut tasks = vec![];
+    let res = {
+      // a board write still open when `persist` drops the journal could
+      // lose pixels, and two writers would share the board file
+      let _persisting = state.persisting.lock().await;
+      flush_board(&state).await
+    };
 
-    for pixel in &state.board {
-      let old_pixel = old_board.get(pixel.0).unwrap();
-      let now_pixel = pixel.1.lock();
+    delay = match res {
+      Ok(()) => {
+        state.health.lock().board.success();
+        tracing::info!("Save board success!");
 
-      if old_pixel.time != now_pixel.time {
-        // is it accurate enough?
-        tasks.push(board::ActiveModel {
-          x: ActiveValue::set(now_pixel.x),
-          y: ActiveValue::set(now_pixel.y),
-          color: ActiveValue::set(now_pixel.color.clone()),
-          uid: ActiveValue::set(now_pixel.uid),
-          time: ActiveValue::set(now_pixel.time),
-        });
+        SAVE_BOARD_INTERVAL
+      }
+      Err(err) => {
+        let failures = state.health.lock().board.failure(&err);
+        tracing::error!(failures, "Save board failed: {err}");
 
-        old_board.insert(pixel.0.to_owned(), now_pixel.to_owned());
+        backoff(failures, SAVE_BOARD_INTERVAL)
       }
+    };
+  }
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn save_actions(state: Arc<AppState>) {
+  let mut delay = SAVE_ACTIONS_INTERVAL;
+
+  loop {
+    tokio::select! {
+      _ = tokio::time::sleep(delay) => { },
+      _ = state.stopping() => break,
     }
 
-    tracing::info!(len = tasks.len(), "Diff board");
+    tracing::info!("Start saving actions...");
 
-    // TODO(config)
-    let tasks = tasks
-      .chunks(600) // pack 600 pixels per task
-      .map(|chunk| chunk.to_owned())
-      .collect::<Vec<Vec<board::ActiveModel>>>();
+    delay = match persist(&state).await {
+      Ok(()) => {
+        state.health.lock().actions.success();
+        tracing::info!("Save actions success!");
 
-    for task in tasks {
-      let res = Board::insert_many(task)
+        SAVE_ACTIONS_INTERVAL
+      }
+      Err(err) => {
+        let failures = state.health.lock().actions.failure(&err);
+        tracing::error!(failures, "Save actions failed: {err}");
+
+        backoff(failures, SAVE_ACTIONS_INTERVAL)
+      }
+    };
+  }
+}
+
+/// Save all queued actions and dirty pixels, then drop the journal
+/// segments covering them.
+pub async fn persist(state: &AppState) -> Result<(), DbErr> {
+  let _persisting = state.persisting.lock().await;
+
+  let seq = save_paints(state).await?;
+
+  // not covered by the journal, a failure must not keep it
+  if let Err(err) = flush_shadow_actions(state).await {
+    tracing::error!("Save shadow paints failed: {err}");
+  }
+
+  // the journal also covers board changes, flush them before dropping it
+  flush_board(state).await?;
+
+  if let Some(seq) = seq {
+    if let Err(err) = state.journal.remove_before(seq) {
+      tracing::error!("Truncate journal failed: {err}");
+    }
+  }
+
+  Ok(())
+}
+
+/// Save all queued actions, keeping the journal for the board.
+pub async fn persist_paints(state: &AppState) -> Result<(), DbErr> {
+  let _persisting = state.persisting.lock().await;
+
+  save_paints(state).await.map(|_| ())
+}
+
+/// Save all queued actions, returns the journal segment all those
+/// before have their paints saved. Callers hold `AppState::persisting`.
+async fn save_paints(state: &AppState) -> Result<Option<u64>, DbErr> {
+  let (actions, seq) = {
+    let mut actions = state.actions.lock();
+
+    // rotate while holding `actions` so that the old segments
+    // contain exactly the actions taken here
+    let seq = state.journal.rotate();
+
+    if let Err(err) = &seq {
+      tracing::error!("Rotate journal failed: {err}");
+    }
+
+    (std::mem::take(&mut *actions), seq.ok())
+  };
+
+  flush_actions(state, actions).await?;
+
+  // the board may still fail, keep the segments for it but never insert
+  // their paints again
+  if let Some(seq) = seq {
+    if let Err(err) = state.journal.mark_saved(seq) {
+      tracing::error!("Mark journal saved failed: {err}");
+    }
+  }
+
+  Ok(seq)
+}
+
+/// Write all dirty pixels to the database in one transaction.
+///
+/// On failure the pixels are marked dirty again. Callers hold
+/// `AppState::persisting`.
+pub async fn flush_board(state: &AppState) -> Result<(), DbErr> {
+  let dirty = state
+    .canvases
+    .values()
+    .map(|canvas| (canvas, std::mem::take(&mut *canvas.dirty.lock())))
+    .collect::<Vec<_>>();
+
+  let tasks = dirty
+    .iter()
+    .flat_map(|(canvas, dirty)| {
+      let board = canvas.board.read();
+
+      dirty
+        .iter()
+        .map(|pos| {
+          let pixel = board.get(pos).unwrap().lock();
+
+          board::ActiveModel {
+            canvas_id: ActiveValue::set(pixel.canvas_id),
+            x: ActiveValue::set(pixel.x),
+            y: ActiveValue::set(pixel.y),
+            color: ActiveValue::set(pixel.color),
+            uid: ActiveValue::set(pixel.uid),
+            time: ActiveValue::set(pixel.time),
+          }
+        })
+        .collect::<Vec<_>>()
+    })
+    .collect::<Vec<_>>();
+
+  tracing::info!(len = tasks.len(), "Diff board");
+
+  let res = async {
+    let txn = state.db.begin().await?;
+
+    // TODO(config)
+    for task in tasks.chunks(600) {
+      // pack 600 pixels per task
+      Board::insert_many(task.to_owned())
         .on_conflict(
-          OnConflict::columns([board::Column::X, board::Column::Y])
+          OnConflict::columns([board::Column::CanvasId, board::Column::X, board::Column::Y])
             .update_columns([
               board::Column::Color,
               board::Column::Uid,
@@ -53,53 +202,123 @@ pub async fn save_board(state: Arc<AppState>, mut old_board: HashMap<(u16, u16),
             ])
             .to_owned(),
         )
-        .exec(&state.db)
-        .await;
+        .exec(&txn)
+        .await?;
+    }
 
-      if res.is_err() {
-        tracing::error!("Save board failed!");
-      }
+    txn.commit().await
+  }
+  .await;
+
+  if res.is_err() {
+    for (canvas, dirty) in dirty {
+      canvas.dirty.lock().extend(dirty);
     }
 
-    tracing::info!("Save board success!");
+    return res;
+  }
+
+  for (canvas, dirty) in dirty {
+    write_board_file(state, canvas, !dirty.is_empty()).await;
   }
+
+  Ok(())
 }
 
-#[tracing::instrument(skip_all)]
-pub async fn save_actions(state: Arc<AppState>) {
-  loop {
-    // TODO(config)
-    // 8 minutes
-    tokio::time::sleep(std::time::Duration::from_secs(480)).await;
+/// Write the board file of `canvas` if the board or its paints changed
+/// since the last one, only logs on failure since the `board` table is
+/// still there to fall back to.
+async fn write_board_file(state: &AppState, canvas: &CanvasState, changed: bool) {
+  // fetch the id before copying the board, so that all paints up to it
+  // are already applied
+  let last_paint_id = match last_paint_id(&state.db, canvas.id).await {
+    Ok(id) => id,
+    Err(err) => {
+      tracing::error!(canvas = canvas.name, "Write board file failed: {err}");
+      return;
+    }
+  };
 
-    tracing::info!("Start saving actions...");
+  if !changed && canvas.file_paint_id.load(Ordering::Relaxed) == last_paint_id {
+    return;
+  }
 
-    let actions = {
-      let mut actions = state.actions.lock();
+  let file = BoardFile {
+    canvas_id: canvas.id,
+    last_paint_id,
+    frame: canvas.frame(),
+  };
+  let name = canvas.name.clone();
 
-      let res = actions.clone();
+  let res = tokio::task::spawn_blocking(move || file.write(&name)).await;
 
-      actions.clear();
+  match res {
+    Ok(Ok(())) => canvas.file_paint_id.store(last_paint_id, Ordering::Relaxed),
+    Ok(Err(err)) => tracing::error!(canvas = canvas.name, "Write board file failed: {err}"),
+    Err(_) => {}
+  }
+}
 
-      res
-    };
+/// Insert `actions` into the database in one transaction.
+///
+/// On failure the actions are put back in front of the queue.
+pub async fn flush_actions(
+  state: &AppState,
+  actions: Vec<paint::ActiveModel>,
+) -> Result<(), DbErr> {
+  tracing::info!(num = actions.len(), "Count actions");
 
-    tracing::info!(num = actions.len(), "Count actions");
+  let res = async {
+    let txn = state.db.begin().await?;
 
     // TODO(config)
-    let tasks = actions
-      .chunks(600) // pack 600 actions per task
-      .map(|chunk| chunk.to_owned())
-      .collect::<Vec<Vec<paint::ActiveModel>>>();
+    for task in actions.chunks(600) {
+      // pack 600 actions per task
+      Paint::insert_many(task.to_owned()).exec(&txn).await?;
+    }
 
-    for task in tasks {
-      let res = Paint::insert_many(task).exec(&state.db).await;
+    txn.commit().await
+  }
+  .await;
 
-      if res.is_err() {
-        tracing::error!("Save actions failed!");
-      }
+  if res.is_err() {
+    let mut queue = state.actions.lock();
+    let newer = std::mem::replace(&mut *queue, actions);
+    queue.extend(newer);
+  }
+
+  res
+}
+
+/// Insert the queued paints of shadowbanned users.
+///
+/// On failure they are put back in front of the queue.
+async fn flush_shadow_actions(state: &AppState) -> Result<(), DbErr> {
+  let actions = std::mem::take(&mut *state.shadow_actions.lock());
+
+  if actions.is_empty() {
+    return Ok(());
+  }
+
+  tracing::info!(num = actions.len(), "Count shadow paints");
+
+  let res = async {
+    let txn = state.db.begin().await?;
+
+    // TODO(config)
+    for task in actions.chunks(600) {
+      ShadowPaint::insert_many(task.to_owned()).exec(&txn).await?;
     }
 
-    tracing::info!("Save actions success!");
+    txn.commit().await
   }
+  .await;
+
+  if res.is_err() {
+    let mut queue = state.shadow_actions.lock();
+    let newer = std::mem::replace(&mut *queue, actions);
+    queue.extend(newer);
+  }
+
+  res
 }
@@ -0,0 +1,159 @@
+use std::{
+  collections::HashMap,
+  net::SocketAddr,
+  sync::atomic::{AtomicU64, Ordering},
+};
+
+use axum::http::HeaderMap;
+use chrono::Local;
+use parking_lot::Mutex;
+use serde::Serialize;
+use tokio::sync::Notify;
+
+/// Sent to every websocket, see `AppState::notices`.
+#[derive(Clone, Debug)]
+pub enum Notice {
+  /// Shown to the clients of `canvas`, or of all canvases if `None`.
+  Announce {
+    canvas: Option<String>,
+    message: String,
+  },
+  /// Close all sessions of a user.
+  Kick(i32),
+  /// The board was frozen or unfrozen.
+  Freeze(bool),
+  /// Ask all sessions of a user to solve a captcha.
+  Challenge(i32),
+  /// A canvas grew to a new size.
+  Resize {
+    canvas: String,
+    width: u16,
+    height: u16,
+  },
+}
+
+/// A live websocket, listed by `GET /admin/sessions`.
+#[derive(Clone, Serialize)]
+pub struct SessionInfo {
+  id: u64,
+  canvas: String,
+  ip: String,
+  /// `None` until authenticated.
+  uid: Option<i32>,
+  /// RFC 3339
+  connected_at: String,
+}
+
+#[derive(Default)]
+pub struct Sessions {
+  next_id: AtomicU64,
+  live: Mutex<HashMap<u64, SessionInfo>>,
+  closed: Notify,
+}
+
+impl Sessions {
+  /// Register a new session, returns its id.
+  pub fn open(&self, canvas: &str, ip: String) -> u64 {
+    let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+
+    let info = SessionInfo {
+      id,
+      canvas: canvas.to_owned(),
+      ip,
+      uid: None,
+      connected_at: Local::now().to_rfc3339(),
+    };
+
+    self.live.lock().insert(id, info);
+
+    id
+  }
+
+  pub fn authenticate(&self, id: u64, uid: i32) {
+    if let Some(info) = self.live.lock().get_mut(&id) {
+      info.uid = Some(uid);
+    }
+  }
+
+  pub fn close(&self, id: u64) {
+    self.live.lock().remove(&id);
+    self.closed.notify_waiters();
+  }
+
+  /// Resolves once no session is left.
+  pub async fn drained(&self) {
+    loop {
+      // registered before checking, so that no close is missed
+      let closed = self.closed.notified();
+
+      if self.live.lock().is_empty() {
+        return;
+      }
+
+      closed.await;
+    }
+  }
+
+  pub fn list(&self) -> Vec<SessionInfo> {
+    let mut res = self.live.lock().values().cloned().collect::<Vec<_>>();
+    res.sort_by_key(|info| info.id);
+    res
+  }
+
+  /// The number of sessions of `uid`.
+  pub fn count_of(&self, uid: i32) -> usize {
+    self
+      .live
+      .lock()
+      .values()
+      .filter(|info| info.uid == Some(uid))
+      .count()
+  }
+}
+
+/// The address of the client, taken from `X-Forwarded-For` when behind
+/// a reverse proxy on the same host.
+///
+/// Only the last entry is used, the one the proxy appended; earlier ones
+/// come from the client and may be forged.
+pub fn client_ip(headers: &HeaderMap, addr: SocketAddr) -> String {
+  if !addr.ip().is_loopback() {
+    return addr.ip().to_string();
+  }
+
+  headers
+    .get_all("x-forwarded-for")
+    .iter()
+    .next_back()
+    .and_then(|value| value.to_str().ok())
+    .and_then(|value| value.rsplit(',').next())
+    .map(|ip| ip.trim().to_owned())
+    .filter(|ip| !ip.is_empty())
+    .unwrap_or_else(|| addr.ip().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn headers(value: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert("x-forwarded-for", value.parse().unwrap());
+    headers
+  }
+
+  #[test]
+  fn forged_entries_are_ignored() {
+    let addr = "127.0.0.1:4000".parse().unwrap();
+
+    assert_eq!(client_ip(&headers("6.6.6.6, 1.2.3.4"), addr), "1.2.3.4");
+    assert_eq!(client_ip(&HeaderMap::new(), addr), "127.0.0.1");
+  }
+
+  #[test]
+  fn header_is_ignored_from_remote_peers() {
+    let addr = "5.6.7.8:4000".parse().unwrap();
+
+    assert_eq!(client_ip(&headers("1.2.3.4"), addr), "5.6.7.8");
+  }
+}
@@ -1,13 +1,14 @@
 mod read;
 
-use std::{sync::Arc, time::Duration};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 
 use axum::{
   extract::{
-    ws::{Message, WebSocket},
-    State, WebSocketUpgrade,
+    ws::{close_code, CloseFrame, Message, WebSocket},
+    ConnectInfo, Path, State, WebSocketUpgrade,
   },
-  response::Response,
+  http::{HeaderMap, StatusCode},
+  response::{IntoResponse, Response},
 };
 use futures::{
   stream::{SplitSink, SplitStream},
@@ -15,43 +16,144 @@ use futures::{
 };
 use parking_lot::Mutex;
 
-use crate::AppState;
+use crate::{
+  bots::Watch,
+  canvas::CanvasState,
+  session::{client_ip, Notice},
+  AppState,
+};
 use read::handle_read;
 use yur_paintboard::pixel::Pixel;
 
-pub async fn ws(State(state): State<Arc<AppState>>, ws: WebSocketUpgrade) -> Response {
-  ws.on_upgrade(|socket| handle_ws(state, socket))
+pub async fn ws(
+  State(state): State<Arc<AppState>>,
+  ConnectInfo(addr): ConnectInfo<SocketAddr>,
+  headers: HeaderMap,
+  ws: WebSocketUpgrade,
+) -> Response {
+  let canvas = state.default_canvas.clone();
+  let ip = client_ip(&headers, addr);
+  upgrade(state, canvas, ip, ws)
+}
+
+pub async fn ws_canvas(
+  State(state): State<Arc<AppState>>,
+  Path(canvas): Path<String>,
+  ConnectInfo(addr): ConnectInfo<SocketAddr>,
+  headers: HeaderMap,
+  ws: WebSocketUpgrade,
+) -> Response {
+  let ip = client_ip(&headers, addr);
+  upgrade(state, canvas, ip, ws)
+}
+
+fn upgrade(state: Arc<AppState>, canvas: String, ip: String, ws: WebSocketUpgrade) -> Response {
+  if !state.canvases.contains_key(&canvas) {
+    return StatusCode::NOT_FOUND.into_response();
+  }
+
+  ws.on_upgrade(|socket| handle_ws(state, canvas, ip, socket))
+}
+
+/// Why a paint was refused, sent to the client as `0xf6`.
+pub enum Reject {
+  Protected(String),
+  /// With the reason, if any.
+  Banned(Option<String>),
+  Frozen,
+  /// The user was challenged by the bot detection.
+  Captcha,
+  /// No paint of the user can be undone.
+  Undo,
+}
+
+impl Reject {
+  fn code(&self) -> u8 {
+    match self {
+      Reject::Protected(_) => 0x01,
+      Reject::Banned(_) => 0x02,
+      Reject::Frozen => 0x03,
+      Reject::Captcha => 0x04,
+      Reject::Undo => 0x05,
+    }
+  }
+
+  fn message(&self) -> String {
+    match self {
+      Reject::Protected(region) => format!("Protected region: {region}"),
+      Reject::Banned(Some(reason)) => format!("Banned: {reason}"),
+      Reject::Banned(None) => "Banned".to_owned(),
+      Reject::Frozen => "The board is frozen".to_owned(),
+      Reject::Captcha => "Solve the captcha and reconnect".to_owned(),
+      Reject::Undo => "Nothing to undo".to_owned(),
+    }
+  }
+
+  /// `0xf6`, x, y, reason code, then a readable message.
+  pub fn to_bytes(&self, x: u16, y: u16) -> Vec<u8> {
+    let mut res = vec![0xf6];
+
+    res.extend_from_slice(&x.to_le_bytes());
+    res.extend_from_slice(&y.to_le_bytes());
+    res.push(self.code());
+    res.extend_from_slice(self.message().as_bytes());
+
+    res
+  }
+}
+
+/// `0xf4`, then `1` if the board is frozen or `0` if not.
+pub fn freeze_bytes(frozen: bool) -> Vec<u8> {
+  vec![0xf4, frozen.into()]
 }
 
 pub struct WsState {
+  session: u64,
+  ip: String,
   uid: Option<i32>,
+  role: Option<String>,
   readonly: bool,
   get_pong: bool,
   quick_paint: u8,
   trash_pack: u8,
+  watch: Watch,
 }
 
-#[tracing::instrument(name = "ws", skip_all, fields(uid))]
-async fn handle_ws(state: Arc<AppState>, socket: WebSocket) {
+#[tracing::instrument(name = "ws", skip_all, fields(canvas, ip, uid))]
+async fn handle_ws(state: Arc<AppState>, canvas: String, ip: String, socket: WebSocket) {
+  tracing::Span::current().record("canvas", &canvas);
+  tracing::Span::current().record("ip", &ip);
+
+  let session = state.sessions.open(&canvas, ip.clone());
+
+  let canvas = state.canvases.get(&canvas).unwrap();
   let (ws_out, ws_in) = socket.split();
   let ws_out = tokio::sync::Mutex::new(ws_out);
   let ws_state = WsState {
+    session,
+    ip,
     uid: None,
+    role: None,
     readonly: true,
     get_pong: false,
     quick_paint: 0,
     trash_pack: 0,
+    watch: Watch::new(),
   };
   let ws_state = Mutex::new(ws_state);
   let ws_paints = Mutex::new(vec![]);
 
   tokio::select! {
-    _ = ws_read(ws_in, &ws_out, state.clone(), &ws_state) => { },
-    _ = recv_paint(state, &ws_state, &ws_paints) => { },
+    _ = ws_read(ws_in, &ws_out, state.clone(), canvas, &ws_state) => { },
+    _ = recv_paint(canvas, &ws_state, &ws_paints) => { },
     _ = ws_write(&ws_out, &ws_paints) => { },
     _ = heartbeat(&ws_out, &ws_state) => { },
+    _ = recv_notice(&ws_out, &state, canvas, &ws_state) => { },
+    _ = shutdown(&ws_out, &state) => { },
   }
 
+  state.sessions.close(session);
+
   tracing::info!("Closed.");
 }
 
@@ -59,12 +161,13 @@ async fn ws_read(
   mut ws_in: SplitStream<WebSocket>,
   ws_out: &tokio::sync::Mutex<SplitSink<WebSocket, Message>>,
   state: Arc<AppState>,
+  canvas: &CanvasState,
   ws_state: &Mutex<WsState>,
 ) {
   loop {
     let msg = ws_in.next().await;
 
-    let exit = handle_read(state.clone(), ws_out, ws_state, msg).await;
+    let exit = handle_read(state.clone(), canvas, ws_out, ws_state, msg).await;
 
     if exit {
       break;
@@ -84,20 +187,29 @@ async fn ws_read(
 }
 
 async fn recv_paint(
-  state: Arc<AppState>,
+  canvas: &CanvasState,
   ws_state: &Mutex<WsState>,
   ws_paints: &Mutex<Vec<Pixel>>,
 ) {
-  let mut receiver = state.sender.subscribe();
+  let mut receiver = canvas.sender.subscribe();
+  let mut shadow_receiver = canvas.shadow_sender.subscribe();
 
   loop {
-    let msg = receiver.recv().await;
-
-    if msg.is_err() {
+    let paint = tokio::select! {
+      msg = receiver.recv() => msg,
+      msg = shadow_receiver.recv() => match msg {
+        // echo shadow paints only to the painter
+        Ok((uid, paint)) if ws_state.lock().uid == Some(uid) => Ok(paint),
+        Ok(_) => continue,
+        Err(err) => Err(err),
+      },
+    };
+
+    if paint.is_err() {
       continue;
     }
 
-    let paint = msg.unwrap();
+    let paint = paint.unwrap();
 
     {
       let ws_state = ws_state.lock();
@@ -109,6 +221,102 @@ async fn recv_paint(
   }
 }
 
+async fn recv_notice(
+  ws_out: &tokio::sync::Mutex<SplitSink<WebSocket, Message>>,
+  state: &AppState,
+  canvas: &CanvasState,
+  ws_state: &Mutex<WsState>,
+) {
+  let mut receiver = state.notices.subscribe();
+
+  loop {
+    let notice = match receiver.recv().await {
+      Ok(notice) => notice,
+      Err(_) => continue,
+    };
+
+    match notice {
+      Notice::Announce {
+        canvas: target,
+        message,
+      } => {
+        if target.is_some_and(|target| target != canvas.name) {
+          continue;
+        }
+
+        let mut msg = vec![0xf5];
+        msg.extend_from_slice(message.as_bytes());
+
+        let res = ws_out.lock().await.send(Message::Binary(msg)).await;
+        if res.is_err() {
+          tracing::warn!("Closed due to failed to send announcement");
+          break;
+        }
+      }
+      Notice::Kick(uid) => {
+        if ws_state.lock().uid != Some(uid) {
+          continue;
+        }
+
+        let frame = CloseFrame {
+          code: close_code::POLICY,
+          reason: "Banned".into(),
+        };
+
+        let _ = ws_out.lock().await.send(Message::Close(Some(frame))).await;
+
+        tracing::info!("Closed due to ban");
+        break;
+      }
+      Notice::Challenge(uid) => {
+        if ws_state.lock().uid != Some(uid) {
+          continue;
+        }
+
+        let res = ws_out.lock().await.send(Message::Binary(vec![0xf3])).await;
+        if res.is_err() {
+          tracing::warn!("Closed due to failed to send challenge");
+          break;
+        }
+      }
+      Notice::Resize {
+        canvas: target,
+        width,
+        height,
+      } => {
+        if target != canvas.name {
+          continue;
+        }
+
+        // stop sending paints until the client fetches the new board
+        ws_state.lock().readonly = true;
+
+        let mut msg = vec![0xf1];
+        msg.extend_from_slice(&width.to_le_bytes());
+        msg.extend_from_slice(&height.to_le_bytes());
+
+        let res = ws_out.lock().await.send(Message::Binary(msg)).await;
+        if res.is_err() {
+          tracing::warn!("Closed due to failed to send new size");
+          break;
+        }
+      }
+      Notice::Freeze(frozen) => {
+        let res = ws_out
+          .lock()
+          .await
+          .send(Message::Binary(freeze_bytes(frozen)))
+          .await;
+
+        if res.is_err() {
+          tracing::warn!("Closed due to failed to send freeze state");
+          break;
+        }
+      }
+    }
+  }
+}
+
 async fn ws_write(
   ws_out: &tokio::sync::Mutex<SplitSink<WebSocket, Message>>,
   ws_paints: &Mutex<Vec<Pixel>>,
@@ -178,3 +386,16 @@ async fn heartbeat(
     }
   }
 }
+
+async fn shutdown(ws_out: &tokio::sync::Mutex<SplitSink<WebSocket, Message>>, state: &AppState) {
+  state.stopping().await;
+
+  let frame = CloseFrame {
+    code: close_code::AWAY,
+    reason: "Server shutting down".into(),
+  };
+
+  let _ = ws_out.lock().await.send(Message::Close(Some(frame))).await;
+
+  tracing::info!("Closed due to shutdown");
+}
@@ -1,23 +1,27 @@
-use std::sync::Arc;
+use std::sync::{atomic::Ordering, Arc};
 
 use axum::extract::ws::{Message, WebSocket};
-use chrono::Local;
+use chrono::{DateTime, Local};
 use futures::{stream::SplitSink, SinkExt};
-use jsonwebtoken::{decode, Algorithm, Validation};
 use parking_lot::Mutex;
-use sea_orm::ActiveValue;
-use serde::Deserialize;
-
-use super::WsState;
-use crate::AppState;
+use sea_orm::{ActiveModelTrait, ActiveValue, DbErr};
+
+use super::{freeze_bytes, Reject, WsState};
+use crate::{
+  auth::{decode_token, Claims},
+  bots::flag_bot,
+  canvas::{CanvasState, LastPaint},
+  AppState,
+};
 use yur_paintboard::{
-  consts::{BEGIN_TIME, END_TIME, HEIGHT, WIDTH},
-  entities::{board, paint},
-  pixel::{color_to_hex, hex_to_bin, Pixel},
+  detect::Sample,
+  entities::{board, paint::PaintKind, session_log},
+  pixel::{color_to_int, int_to_bin, Pixel},
 };
 
 pub async fn handle_read(
   state: Arc<AppState>,
+  canvas: &CanvasState,
   ws_out: &tokio::sync::Mutex<SplitSink<WebSocket, Message>>,
   ws_state: &Mutex<WsState>,
   msg: Option<Result<Message, axum::Error>>,
@@ -51,21 +55,45 @@ pub async fn handle_read(
   match opt {
     0xff => {
       // Auth
-      let mut uid = ws_state.lock().uid;
-
-      if uid.is_some() {
+      if ws_state.lock().uid.is_some() {
         tracing::warn!("Duplicated auth!");
         ws_state.lock().trash_pack += 1;
         return false;
       }
 
-      uid = handle_auth(state, data).await;
+      let claims = handle_auth(state.clone(), data).await;
+
+      match claims {
+        Some(claims) => {
+          let uid = claims.uid;
+
+          {
+            let mut ws_state = ws_state.lock();
+            ws_state.uid = Some(uid);
+            ws_state.role = claims.role;
+            state.sessions.authenticate(ws_state.session, uid);
+          }
+
+          if let Some(solved) = claims.captcha {
+            let mut challenged = state.challenged.lock();
+
+            if challenged
+              .get(&uid)
+              .is_some_and(|asked| solved >= asked.timestamp())
+            {
+              tracing::info!("Solved captcha.");
+              challenged.remove(&uid);
+            }
+          }
 
-      match uid {
-        Some(uid) => {
-          ws_state.lock().uid = Some(uid);
           tracing::Span::current().record("uid", uid);
 
+          let ip = ws_state.lock().ip.clone();
+
+          if let Err(err) = log_session(&state, canvas, uid, ip).await {
+            tracing::error!("Write session log failed: {err}");
+          }
+
           let res = ws_out.lock().await.send(Message::Binary(vec![0xfc])).await; // auth success
           if res.is_err() {
             tracing::warn!("Error sending auth result, closing...");
@@ -95,7 +123,39 @@ pub async fn handle_read(
         return false;
       }
 
-      handle_paint(state, ws_state, data).await;
+      if let Err((x, y, reject)) = handle_paint(state, canvas, ws_state, data).await {
+        let res = ws_out
+          .lock()
+          .await
+          .send(Message::Binary(reject.to_bytes(x, y)))
+          .await;
+
+        if res.is_err() {
+          tracing::warn!("Error sending rejection, closing...");
+          return true;
+        }
+      }
+    }
+    0xf2 => {
+      // Undo
+      if ws_state.lock().uid.is_none() {
+        tracing::warn!("Undo without auth!");
+        ws_state.lock().trash_pack += 1;
+        return false;
+      }
+
+      if let Err((x, y, reject)) = handle_undo(state, canvas, ws_state) {
+        let res = ws_out
+          .lock()
+          .await
+          .send(Message::Binary(reject.to_bytes(x, y)))
+          .await;
+
+        if res.is_err() {
+          tracing::warn!("Error sending rejection, closing...");
+          return true;
+        }
+      }
     }
     0xf9 => {
       // Board
@@ -113,7 +173,7 @@ pub async fn handle_read(
         return true;
       }
 
-      let board = get_board(state);
+      let board = get_board(canvas);
 
       ws_state.lock().readonly = false;
 
@@ -124,6 +184,19 @@ pub async fn handle_read(
       }
 
       tracing::info!("Sent board.");
+
+      if state.frozen.load(Ordering::Relaxed) {
+        let res = ws_out
+          .lock()
+          .await
+          .send(Message::Binary(freeze_bytes(true)))
+          .await;
+
+        if res.is_err() {
+          tracing::warn!("Error sending freeze state, closing...");
+          return true;
+        }
+      }
     }
     0xf7 => {
       // Pong
@@ -136,18 +209,11 @@ pub async fn handle_read(
     }
   }
 
-  return false;
-}
-
-#[derive(Deserialize)]
-struct Claims {
-  #[allow(dead_code)]
-  exp: usize,
-  uid: i32,
+  false
 }
 
 #[tracing::instrument(name = "auth", skip_all)]
-pub async fn handle_auth(state: Arc<AppState>, data: &[u8]) -> Option<i32> {
+pub async fn handle_auth(state: Arc<AppState>, data: &[u8]) -> Option<Claims> {
   let raw_token = std::str::from_utf8(data);
 
   if raw_token.is_err() {
@@ -156,56 +222,124 @@ pub async fn handle_auth(state: Arc<AppState>, data: &[u8]) -> Option<i32> {
   }
   let raw_token = raw_token.unwrap();
 
-  let token = decode::<Claims>(raw_token, &state.pubkey, &Validation::new(Algorithm::EdDSA));
+  decode_token(&state, raw_token)
+}
 
-  if let Err(err) = token {
-    tracing::warn!(token = raw_token, "Invalid token: {err}");
-    return None;
+/// Record who connected from where, for finding accounts run together.
+async fn log_session(
+  state: &AppState,
+  canvas: &CanvasState,
+  uid: i32,
+  ip: String,
+) -> Result<(), DbErr> {
+  let log = session_log::ActiveModel {
+    uid: ActiveValue::set(uid),
+    canvas_id: ActiveValue::set(canvas.id),
+    ip: ActiveValue::set(ip),
+    time: ActiveValue::set(Local::now()),
+    ..Default::default()
+  };
+
+  log.insert(&state.db).await?;
+
+  Ok(())
+}
+
+/// Checks shared by paints and undos of the pixel at `x`, `y`.
+fn check_rules(
+  state: &AppState,
+  canvas: &CanvasState,
+  ws_state: &Mutex<WsState>,
+  uid: i32,
+  x: u16,
+  y: u16,
+  now: DateTime<Local>,
+) -> Result<(), (u16, u16, Reject)> {
+  if state.frozen.load(Ordering::Relaxed) {
+    tracing::info!("Painting while frozen");
+    return Err((x, y, Reject::Frozen));
+  }
+
+  if let Some(ban) = state.ban_of(uid, now) {
+    tracing::warn!("Painting while banned");
+    return Err((x, y, Reject::Banned(ban.reason)));
+  }
+
+  if state.challenged.lock().contains_key(&uid) {
+    tracing::info!("Painting before solving captcha");
+    return Err((x, y, Reject::Captcha));
+  }
+
+  let region = {
+    let ws_state = ws_state.lock();
+    canvas
+      .protected_by(x, y, uid, ws_state.role.as_deref(), now)
+      .map(|region| region.name.clone())
+  };
+
+  if let Some(region) = region {
+    tracing::warn!(x, y, region, "Painting in a protected region");
+    return Err((x, y, Reject::Protected(region)));
   }
-  let token = token.unwrap();
 
-  Some(token.claims.uid)
+  Ok(())
 }
 
 #[tracing::instrument(name = "paint", skip_all)]
-pub async fn handle_paint(state: Arc<AppState>, ws_state: &Mutex<WsState>, data: &[u8]) {
+pub async fn handle_paint(
+  state: Arc<AppState>,
+  canvas: &CanvasState,
+  ws_state: &Mutex<WsState>,
+  data: &[u8],
+) -> Result<(), (u16, u16, Reject)> {
   if data.len() != 7 {
     tracing::warn!(len = data.len(), "Invalid paint data!");
     ws_state.lock().trash_pack += 1;
-    return;
+    return Ok(());
   }
 
   let x = u16::from_le_bytes([data[0], data[1]]);
 
-  if x >= WIDTH {
+  if x >= canvas.width() {
     tracing::warn!(x, "Invalid paint data!");
     ws_state.lock().trash_pack += 1;
-    return;
+    return Ok(());
   }
 
   let y = u16::from_le_bytes([data[2], data[3]]);
 
-  if y >= HEIGHT {
+  if y >= canvas.height() {
     tracing::warn!(y, "Invalid paint data!");
     ws_state.lock().trash_pack += 1;
-    return;
+    return Ok(());
   }
 
   let color = (data[4], data[5], data[6]);
+  let int_color = color_to_int(color);
+
+  if let Some(palette) = &canvas.palette {
+    if !palette.contains(&int_color) {
+      tracing::warn!(color = int_color, "Color not in palette!");
+      ws_state.lock().trash_pack += 1;
+      return Ok(());
+    }
+  }
 
   let uid = ws_state.lock().uid.unwrap();
 
   let now = Local::now();
 
-  if now.time() < *BEGIN_TIME || now.time() > *END_TIME {
+  if !canvas.is_open(now) {
     tracing::warn!("Painting outside the specified time");
     ws_state.lock().trash_pack += 1;
-    return;
+    return Ok(());
   }
 
+  check_rules(&state, canvas, ws_state, uid, x, y, now)?;
+
   // check interval
   let last_paint = {
-    let user_paint = state.user_paint.lock();
+    let user_paint = canvas.user_paint.lock();
     user_paint.get(&uid).map(|item| item.to_owned())
   };
 
@@ -215,59 +349,162 @@ pub async fn handle_paint(state: Arc<AppState>, ws_state: &Mutex<WsState>, data:
     if (now - last_paint) < chrono::Duration::milliseconds(100) {
       tracing::info!("Quick paint");
       ws_state.quick_paint += 1;
-      return;
+      return Ok(());
     } else {
       ws_state.quick_paint = 0;
     }
   }
 
-  state.user_paint.lock().insert(uid, now);
-
-  let hex_color = color_to_hex(color);
+  let previous_paint = canvas.user_paint.lock().insert(uid, now);
 
   let new_pixel = board::Model {
+    canvas_id: canvas.id,
     x: x.into(),
     y: y.into(),
-    color: hex_color.clone(),
+    color: int_color,
     uid,
     time: now,
   };
 
-  let same = {
-    // same color
-    let mut pixel = state.board.get(&(x, y)).unwrap().lock();
+  let shadow = state.shadowbans.lock().contains(&uid);
 
-    let same = pixel.color == new_pixel.color;
+  let previous = if shadow {
+    let previous = canvas.board.read().get(&(x, y)).unwrap().lock().clone();
+    state.apply_shadow_paint(canvas, new_pixel);
+    previous
+  } else {
+    let mut previous = None;
 
-    *pixel = new_pixel;
+    state.apply_paint(canvas, new_pixel, PaintKind::Paint, |pixel| {
+      previous = Some(pixel.clone());
+      true
+    });
 
-    same
+    previous.unwrap()
   };
 
-  let new_action = paint::ActiveModel {
-    x: ActiveValue::set(x.into()),
-    y: ActiveValue::set(y.into()),
-    color: ActiveValue::set(hex_color),
-    uid: ActiveValue::set(uid),
-    time: ActiveValue::set(now),
-    ..Default::default()
+  let last_paint = LastPaint {
+    x,
+    y,
+    time: now,
+    previous,
+    previous_paint,
+    shadow,
+  };
+
+  canvas.last_paint.lock().insert(uid, last_paint);
+
+  let sample = Sample {
+    time: now,
+    x: x.into(),
+    y: y.into(),
+  };
+
+  let verdict = ws_state.lock().watch.observe(sample);
+
+  if let Some(verdict) = verdict {
+    flag_bot(&state, canvas, uid, verdict).await;
+  }
+
+  Ok(())
+}
+
+/// Revert the latest paint of the user if it is recent enough and
+/// nobody painted the pixel since.
+#[tracing::instrument(name = "undo", skip_all)]
+pub fn handle_undo(
+  state: Arc<AppState>,
+  canvas: &CanvasState,
+  ws_state: &Mutex<WsState>,
+) -> Result<(), (u16, u16, Reject)> {
+  let uid = ws_state.lock().uid.unwrap();
+  let now = Local::now();
+
+  let pos = canvas
+    .last_paint
+    .lock()
+    .get(&uid)
+    .map(|last_paint| (last_paint.x, last_paint.y));
+
+  let (x, y) = match pos {
+    Some(pos) => pos,
+    None => return Err((0, 0, Reject::Undo)),
+  };
+
+  if !canvas.is_open(now) {
+    tracing::warn!("Undoing outside the specified time");
+    ws_state.lock().trash_pack += 1;
+    return Ok(());
+  }
+
+  // a refused undo may be tried again
+  check_rules(&state, canvas, ws_state, uid, x, y, now)?;
+
+  let last_paint = canvas.last_paint.lock().remove(&uid);
+
+  let last_paint = match last_paint {
+    Some(last_paint) => last_paint,
+    None => return Err((x, y, Reject::Undo)),
   };
 
-  state.actions.lock().push(new_action);
+  // TODO(config)
+  if now - last_paint.time > chrono::Duration::seconds(10) {
+    tracing::info!(x, y, "Undo too late");
+    return Err((x, y, Reject::Undo));
+  }
+
+  if last_paint.shadow {
+    // the board was never changed, show it as it is again
+    let pixel = canvas.board.read().get(&(x, y)).unwrap().lock().clone();
+    let [r, g, b] = int_to_bin(pixel.color);
+
+    // fails only if nobody is listening
+    let _ = canvas.shadow_sender.send((
+      uid,
+      Pixel {
+        x,
+        y,
+        color: (r, g, b),
+      },
+    ));
+  } else {
+    // the pixel is exactly as before the paint, as if it never happened,
+    // skipping pixels painted over in the meantime
+    let applied = state.apply_undo(canvas, last_paint.previous, uid, now, |pixel| {
+      pixel.uid == uid && pixel.time == last_paint.time
+    });
+
+    if !applied {
+      tracing::info!(x, y, "Undo after being painted over");
+      return Err((x, y, Reject::Undo));
+    }
+  }
+
+  if state.undo_refund {
+    let mut user_paint = canvas.user_paint.lock();
 
-  if !same {
-    state.sender.send(Pixel { x, y, color }).unwrap();
+    match last_paint.previous_paint {
+      Some(time) => user_paint.insert(uid, time),
+      None => user_paint.remove(&uid),
+    };
   }
+
+  tracing::info!(x, y, "Undone.");
+
+  Ok(())
 }
 
-pub fn get_board(state: Arc<AppState>) -> Vec<u8> {
-  let max_len = WIDTH as usize * HEIGHT as usize * 3;
+pub fn get_board(canvas: &CanvasState) -> Vec<u8> {
+  let pixels = canvas.board.read();
+  let (width, height) = (canvas.width(), canvas.height());
+
+  let max_len = width as usize * height as usize * 3;
   let mut board = Vec::with_capacity(max_len);
 
-  for x in 0..WIDTH {
-    for y in 0..HEIGHT {
-      let pixel = state.board.get(&(x, y)).unwrap().lock();
-      let pixel_bytes = hex_to_bin(&pixel.color);
+  for x in 0..width {
+    for y in 0..height {
+      let pixel = pixels.get(&(x, y)).unwrap().lock();
+      let pixel_bytes = int_to_bin(pixel.color);
       board.extend_from_slice(&pixel_bytes);
     }
   }